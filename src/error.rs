@@ -0,0 +1,58 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// what storage operations fail with
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Corruption { file: PathBuf, offset: u64 }, // bytes that fail their checksum or decoding
+    InvalidArgument(String),
+    NotFound(PathBuf),
+    Conflict, // a transaction read a key another write changed before it committed
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn corruption(file: impl Into<PathBuf>, offset: u64) -> Error {
+        Error::Corruption {
+            file: file.into(),
+            offset,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "i/o error: {}", e),
+            Error::Corruption { file, offset } => {
+                write!(
+                    f,
+                    "corrupted data in {} at offset {}",
+                    file.display(),
+                    offset
+                )
+            }
+            Error::InvalidArgument(message) => write!(f, "invalid argument: {}", message),
+            Error::NotFound(file) => write!(f, "{} not found", file.display()),
+            Error::Conflict => write!(f, "transaction conflicts with a later write"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
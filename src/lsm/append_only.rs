@@ -0,0 +1,82 @@
+use std::borrow::Cow;
+use std::cell::{Cell, OnceCell};
+
+// chunk i holds 2^i values, enough chunks for any index
+const CHUNK_COUNT: usize = usize::BITS as usize;
+
+// Append-only list handing out shared references to values that were read from disk,
+// so they can be returned by ST methods that borrow from the database itself. Every
+// value is written once into a chunk that is never moved or resized afterwards, and
+// values are only released by clear, which takes the list mutably once no reference
+// to them is alive anymore.
+pub struct AppendOnly<T> {
+    chunks: [OnceCell<Box<[OnceCell<T>]>>; CHUNK_COUNT],
+    len: Cell<usize>,
+}
+
+impl<T> AppendOnly<T> {
+    pub fn new() -> AppendOnly<T> {
+        AppendOnly {
+            chunks: std::array::from_fn(|_| OnceCell::new()),
+            len: Cell::new(0),
+        }
+    }
+
+    pub fn alloc(&self, value: T) -> &T {
+        // counting from one, the highest bit of the count numbers the chunk
+        let count = self.len.get() + 1;
+        self.len.set(count);
+
+        let chunk = (usize::BITS - 1 - count.leading_zeros()) as usize;
+        let slots =
+            self.chunks[chunk].get_or_init(|| (0..1 << chunk).map(|_| OnceCell::new()).collect());
+
+        slots[count - (1 << chunk)].get_or_init(|| value)
+    }
+
+    // reference to a borrowed value as is, to an owned one once it is allocated
+    pub fn lend<'a>(&'a self, value: Cow<'a, T>) -> &'a T
+    where
+        T: Clone,
+    {
+        match value {
+            Cow::Borrowed(value) => value,
+            Cow::Owned(value) => self.alloc(value),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = AppendOnly::new();
+    }
+}
+
+impl<T> Default for AppendOnly<T> {
+    fn default() -> AppendOnly<T> {
+        AppendOnly::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references_remain_valid_while_the_list_grows() {
+        // arrange
+        let list = &mut AppendOnly::new();
+
+        // act
+        let first = list.alloc(String::from("first"));
+        let others: Vec<&String> = (0..1000).map(|i| list.alloc(format!("{}", i))).collect();
+
+        // assert
+        assert_eq!(first, "first");
+        assert!(others
+            .iter()
+            .enumerate()
+            .all(|(i, other)| **other == format!("{}", i)));
+
+        list.clear();
+        assert_eq!(list.alloc(String::from("again")), "again");
+    }
+}
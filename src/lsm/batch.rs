@@ -0,0 +1,51 @@
+use crate::lsm::wal::LogRecord;
+
+// Puts and deletes applied together: the log holds them as a single record, so after a
+// crash either all of them or none are visible. Later operations on a key win over
+// earlier ones of the same batch.
+pub struct WriteBatch<KEY, VALUE> {
+    operations: Vec<LogRecord<KEY, VALUE>>,
+}
+
+impl<KEY, VALUE> WriteBatch<KEY, VALUE> {
+    pub fn new() -> WriteBatch<KEY, VALUE> {
+        WriteBatch {
+            operations: Vec::new(),
+        }
+    }
+
+    pub fn put(&mut self, key: KEY, value: VALUE) {
+        self.operations.push(LogRecord::Put(key, value));
+    }
+
+    pub fn delete(&mut self, key: KEY) {
+        self.operations.push(LogRecord::Delete(key));
+    }
+
+    pub fn merge(&mut self, key: KEY, operand: VALUE) {
+        self.operations.push(LogRecord::Merge(key, operand));
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    // operations in the order they were added
+    pub fn operations(&self) -> &[LogRecord<KEY, VALUE>] {
+        &self.operations
+    }
+
+    pub fn into_operations(self) -> Vec<LogRecord<KEY, VALUE>> {
+        self.operations
+    }
+}
+
+impl<KEY, VALUE> Default for WriteBatch<KEY, VALUE> {
+    fn default() -> Self {
+        WriteBatch::new()
+    }
+}
@@ -0,0 +1,325 @@
+use crate::error::Result;
+use std::any::Any;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+const SHARDS: usize = 16;
+
+// blocks are identified by the table they belong to and their offset in its file
+pub type BlockKey = (u64, u64);
+
+type CachedBlock = Arc<dyn Any + Send + Sync>;
+
+#[derive(Clone, Copy)]
+pub enum BlockKind {
+    Data,
+    Index,
+    Filter,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BlockStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl BlockStats {
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub data: BlockStats,
+    pub index: BlockStats,
+    pub filter: BlockStats,
+    pub usage: usize, // bytes of the cached blocks
+}
+
+#[derive(Default)]
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Counters {
+    fn stats(&self) -> BlockStats {
+        BlockStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Decoded table blocks, shared by every table of a database and bounded by the bytes the
+// blocks take on disk. Keys are spread over shards, each with its own lock and its own
+// least recently used order, so that readers of different blocks rarely wait on each
+// other.
+pub struct BlockCache {
+    shards: Vec<Mutex<Shard>>,
+    next_file_id: AtomicU64,
+    data: Counters,
+    index: Counters,
+    filter: Counters,
+}
+
+impl BlockCache {
+    pub fn new(capacity: usize) -> Arc<BlockCache> {
+        let shard_capacity = capacity.div_ceil(SHARDS);
+
+        Arc::new(BlockCache {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(Shard::new(shard_capacity)))
+                .collect(),
+            next_file_id: AtomicU64::new(1),
+            data: Counters::default(),
+            index: Counters::default(),
+            filter: Counters::default(),
+        })
+    }
+
+    // id under which a table caches its blocks, never given out twice
+    pub fn new_file_id(&self) -> u64 {
+        self.next_file_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // the cached block, or the one load returns along with its size in bytes
+    pub fn get_or_load<T, F>(&self, key: BlockKey, kind: BlockKind, load: F) -> Result<Arc<T>>
+    where
+        T: Any + Send + Sync,
+        F: FnOnce() -> Result<(T, usize)>,
+    {
+        let counters = match kind {
+            BlockKind::Data => &self.data,
+            BlockKind::Index => &self.index,
+            BlockKind::Filter => &self.filter,
+        };
+
+        let shard = &self.shards[shard_of(key)];
+        let cached = shard.lock().unwrap().get(key);
+
+        if let Some(block) = cached.and_then(|block| block.downcast::<T>().ok()) {
+            counters.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(block);
+        }
+
+        counters.misses.fetch_add(1, Ordering::Relaxed);
+
+        // loaded without holding the lock, a concurrent reader may load the block too
+        let (block, charge) = load()?;
+        let block = Arc::new(block);
+        shard.lock().unwrap().insert(key, block.clone(), charge);
+
+        Ok(block)
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            data: self.data.stats(),
+            index: self.index.stats(),
+            filter: self.filter.stats(),
+            usage: self
+                .shards
+                .iter()
+                .map(|shard| shard.lock().unwrap().usage)
+                .sum(),
+        }
+    }
+}
+
+fn shard_of((file_id, offset): BlockKey) -> usize {
+    let mixed =
+        (file_id.wrapping_mul(0x9e37_79b9_7f4a_7c15) ^ offset).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    (mixed >> 32) as usize % SHARDS
+}
+
+const NIL: usize = usize::MAX;
+
+struct Entry {
+    key: BlockKey,
+    block: Option<CachedBlock>, // none once the entry is free
+    charge: usize,
+    newer: usize,
+    older: usize,
+}
+
+// least recently used list threaded through a slab of entries, newest at the head
+struct Shard {
+    capacity: usize,
+    usage: usize,
+    positions: HashMap<BlockKey, usize>,
+    entries: Vec<Entry>,
+    free: Vec<usize>,
+    newest: usize,
+    oldest: usize,
+}
+
+impl Shard {
+    fn new(capacity: usize) -> Shard {
+        Shard {
+            capacity,
+            usage: 0,
+            positions: HashMap::new(),
+            entries: Vec::new(),
+            free: Vec::new(),
+            newest: NIL,
+            oldest: NIL,
+        }
+    }
+
+    fn get(&mut self, key: BlockKey) -> Option<CachedBlock> {
+        let position = *self.positions.get(&key)?;
+
+        self.unlink(position);
+        self.link_newest(position);
+
+        self.entries[position].block.clone()
+    }
+
+    fn insert(&mut self, key: BlockKey, block: CachedBlock, charge: usize) {
+        if let Some(&position) = self.positions.get(&key) {
+            self.remove(position);
+        }
+
+        let entry = Entry {
+            key,
+            block: Some(block),
+            charge,
+            newer: NIL,
+            older: NIL,
+        };
+
+        let position = match self.free.pop() {
+            Some(position) => {
+                self.entries[position] = entry;
+                position
+            }
+            None => {
+                self.entries.push(entry);
+                self.entries.len() - 1
+            }
+        };
+
+        self.positions.insert(key, position);
+        self.link_newest(position);
+        self.usage += charge;
+
+        while self.usage > self.capacity && self.oldest != NIL {
+            self.remove(self.oldest);
+        }
+    }
+
+    fn remove(&mut self, position: usize) {
+        self.unlink(position);
+
+        let entry = &mut self.entries[position];
+        self.positions.remove(&entry.key);
+        self.usage -= entry.charge;
+
+        // drops the block unless a reader still holds it
+        entry.block = None;
+        self.free.push(position);
+    }
+
+    fn unlink(&mut self, position: usize) {
+        let Entry { newer, older, .. } = self.entries[position];
+
+        match newer {
+            NIL => self.newest = older,
+            newer => self.entries[newer].older = older,
+        }
+
+        match older {
+            NIL => self.oldest = newer,
+            older => self.entries[older].newer = newer,
+        }
+    }
+
+    fn link_newest(&mut self, position: usize) {
+        self.entries[position].newer = NIL;
+        self.entries[position].older = self.newest;
+
+        match self.newest {
+            NIL => self.oldest = position,
+            newest => self.entries[newest].newer = position,
+        }
+
+        self.newest = position;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(value: u64, charge: usize) -> impl FnOnce() -> Result<(u64, usize)> {
+        move || Ok((value, charge))
+    }
+
+    #[test]
+    fn cached_blocks_are_not_loaded_again() {
+        let cache = BlockCache::new(1024);
+
+        let first = cache
+            .get_or_load((1, 0), BlockKind::Data, load(7, 10))
+            .unwrap();
+        let second = cache
+            .get_or_load((1, 0), BlockKind::Data, || -> Result<(u64, usize)> {
+                panic!("block loaded twice")
+            })
+            .unwrap();
+
+        assert_eq!((*first, *second), (7, 7));
+        assert_eq!(cache.stats().data, BlockStats { hits: 1, misses: 1 });
+        assert_eq!(cache.stats().usage, 10);
+    }
+
+    #[test]
+    fn least_recently_used_blocks_are_evicted_first() {
+        let mut shard = Shard::new(30);
+
+        shard.insert((1, 0), Arc::new(0_u64), 10);
+        shard.insert((1, 1), Arc::new(1_u64), 10);
+        shard.insert((1, 2), Arc::new(2_u64), 10);
+        shard.get((1, 0));
+        shard.insert((1, 3), Arc::new(3_u64), 10);
+
+        assert!(shard.get((1, 1)).is_none());
+        assert!(shard.get((1, 0)).is_some());
+        assert!(shard.get((1, 2)).is_some());
+        assert!(shard.get((1, 3)).is_some());
+        assert_eq!(shard.usage, 30);
+
+        shard.insert((1, 4), Arc::new(4_u64), 25);
+
+        assert_eq!(shard.positions.len(), 1);
+        assert_eq!(shard.usage, 25);
+    }
+
+    #[test]
+    fn kinds_of_blocks_are_counted_apart() {
+        let cache = BlockCache::new(1024);
+
+        cache
+            .get_or_load((1, 0), BlockKind::Index, load(1, 1))
+            .unwrap();
+        cache
+            .get_or_load((1, 0), BlockKind::Index, load(1, 1))
+            .unwrap();
+        cache
+            .get_or_load((1, 8), BlockKind::Filter, load(2, 1))
+            .unwrap();
+
+        let stats = cache.stats();
+
+        assert_eq!(stats.index, BlockStats { hits: 1, misses: 1 });
+        assert_eq!(stats.filter, BlockStats { hits: 0, misses: 1 });
+        assert_eq!(stats.data, BlockStats::default());
+        assert_eq!(stats.index.hit_rate(), 0.5);
+    }
+}
@@ -0,0 +1,177 @@
+use std::convert::TryInto;
+
+// how keys and values are turned into bytes when they leave memory
+pub trait Codec: Sized {
+    fn encode(&self, buffer: &mut Vec<u8>);
+    fn decode(bytes: &[u8]) -> Option<Self>;
+
+    fn encoded_len(&self) -> usize {
+        let mut buffer = Vec::new();
+        self.encode(&mut buffer);
+        buffer.len()
+    }
+
+    // part of an encoded key Bloom filters and table lookups tell keys apart by, all of
+    // it unless several keys stand for versions of the same key
+    fn user_key(encoded: &[u8]) -> &[u8] {
+        encoded
+    }
+}
+
+impl Codec for Vec<u8> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Vec<u8>> {
+        Some(bytes.to_vec())
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Codec for String {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<String> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.len()
+    }
+}
+
+impl Codec for u64 {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        buffer.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Option<u64> {
+        Some(u64::from_le_bytes(bytes.try_into().ok()?))
+    }
+
+    fn encoded_len(&self) -> usize {
+        8
+    }
+}
+
+pub fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn put_u64(buffer: &mut Vec<u8>, value: u64) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+pub fn get_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let slice = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(slice.try_into().ok()?))
+}
+
+pub fn get_u64(bytes: &[u8], offset: usize) -> Option<u64> {
+    let slice = bytes.get(offset..offset + 8)?;
+    Some(u64::from_le_bytes(slice.try_into().ok()?))
+}
+
+// writes the value 7 bits at a time, low bits first, with the high bit of every byte but
+// the last one set
+pub fn put_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+// reads a value written by put_varint, returning it and the offset after it
+pub fn get_varint(bytes: &[u8], offset: usize) -> Option<(u64, usize)> {
+    let mut value = 0;
+
+    for (position, &byte) in bytes.get(offset..)?.iter().take(10).enumerate() {
+        value |= ((byte & 0x7f) as u64) << (7 * position);
+
+        if byte < 0x80 {
+            return Some((value, offset + position + 1));
+        }
+    }
+
+    None
+}
+
+// writes a u32 length followed by the encoded item
+pub fn put_length_prefixed<T: Codec>(buffer: &mut Vec<u8>, item: &T) {
+    let start = buffer.len();
+    put_u32(buffer, 0);
+    item.encode(buffer);
+
+    let length = (buffer.len() - start - 4) as u32;
+    buffer[start..start + 4].copy_from_slice(&length.to_le_bytes());
+}
+
+// reads an item written by put_length_prefixed, returning it and the offset after it
+pub fn get_length_prefixed<T: Codec>(bytes: &[u8], offset: usize) -> Option<(T, usize)> {
+    let length = get_u32(bytes, offset)? as usize;
+    let start = offset + 4;
+    let item = T::decode(bytes.get(start..start + length)?)?;
+
+    Some((item, start + length))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_supported_types() {
+        let buffer = &mut Vec::new();
+
+        put_length_prefixed(buffer, &String::from("word"));
+        put_length_prefixed(buffer, &42_u64);
+        put_length_prefixed(buffer, &vec![1_u8, 2, 3]);
+
+        let (word, offset) = get_length_prefixed::<String>(buffer, 0).unwrap();
+        let (count, offset) = get_length_prefixed::<u64>(buffer, offset).unwrap();
+        let (bytes, offset) = get_length_prefixed::<Vec<u8>>(buffer, offset).unwrap();
+
+        assert_eq!(word, "word");
+        assert_eq!(count, 42);
+        assert_eq!(bytes, vec![1, 2, 3]);
+        assert_eq!(offset, buffer.len());
+    }
+
+    #[test]
+    fn varints_take_one_byte_for_every_seven_bits() {
+        let buffer = &mut Vec::new();
+
+        for value in [0, 127, 128, 300, u64::MAX] {
+            put_varint(buffer, value);
+        }
+
+        assert_eq!(buffer.len(), 1 + 1 + 2 + 2 + 10);
+
+        let mut offset = 0;
+        for value in [0, 127, 128, 300, u64::MAX] {
+            let (decoded, next) = get_varint(buffer, offset).unwrap();
+            assert_eq!(decoded, value);
+            offset = next;
+        }
+
+        assert_eq!(get_varint(&[0x80, 0x80], 0), None);
+    }
+
+    #[test]
+    fn decode_rejects_truncated_input() {
+        let buffer = &mut Vec::new();
+        put_length_prefixed(buffer, &String::from("word"));
+
+        assert!(get_length_prefixed::<String>(&buffer[..6], 0).is_none());
+        assert!(u64::decode(&[1, 2, 3]).is_none());
+        assert!(String::decode(&[0xff, 0xfe]).is_none());
+    }
+}
@@ -0,0 +1,319 @@
+use std::ops::Range;
+
+// what a strategy gets to know about a table
+pub struct TableSummary {
+    pub number: u64, // file number, greater for newer tables
+    pub file_size: u64,
+}
+
+// tables to merge, and the level the merged tables go to
+#[derive(Debug, PartialEq)]
+pub struct Compaction {
+    pub level: usize,
+    pub tables: Vec<usize>,          // ascending positions within the level
+    pub output_level: usize,         // the level itself to merge in place, or the next one
+    pub max_table_size: Option<u64>, // merged tables are split once they grow past it
+}
+
+// Level 0 holds flushed tables, newest first, whose key ranges may overlap. Deeper
+// levels hold tables sorted by key, with non-overlapping key ranges, every level older
+// than the one above it. So that the newest version of a key is always found first,
+// tables merged in place must be adjacent, and tables leaving level 0 must be its
+// oldest ones. Strategies are Send so that a database can move to another thread.
+pub trait CompactionStrategy: Send {
+    // next compaction to run, given the tables of every level
+    fn pick(&self, levels: &[Vec<TableSummary>]) -> Option<Compaction>;
+}
+
+// Size-tiered compaction: tables of similar size form a tier, and a tier holding enough
+// tables is merged into a single, larger table. Tables are only grouped with their
+// neighbours in age, so the merged table can take the place of its inputs without
+// changing which version of a key is the newest.
+pub struct SizeTiered {
+    pub min_tables: usize, // tables a tier needs before it is merged
+    pub max_tables: usize, // tables merged at once, at most
+    pub bucket_ratio: f64, // a table joins a tier when within this factor of its average size
+}
+
+impl Default for SizeTiered {
+    fn default() -> SizeTiered {
+        SizeTiered {
+            min_tables: 4,
+            max_tables: 32,
+            bucket_ratio: 1.5,
+        }
+    }
+}
+
+impl SizeTiered {
+    // positions of the tables to merge, given table sizes ordered newest first. The tier
+    // with the smallest tables is picked first, as it is the cheapest to merge.
+    fn tier(&self, sizes: &[u64]) -> Option<Range<usize>> {
+        let mut picked: Option<(Range<usize>, f64)> = None;
+        let mut start = 0;
+
+        while start < sizes.len() {
+            let mut end = start + 1;
+            let mut total = sizes[start] as f64;
+
+            while end < sizes.len() {
+                let average = total / (end - start) as f64;
+                let size = sizes[end] as f64;
+
+                if size < average / self.bucket_ratio || size > average * self.bucket_ratio {
+                    break;
+                }
+
+                total += size;
+                end += 1;
+            }
+
+            if end - start >= self.min_tables {
+                // the oldest tables of the tier, so that tombstones may reach the bottom
+                let tier = end.saturating_sub(self.max_tables).max(start)..end;
+                let average = total / (end - start) as f64;
+
+                if picked
+                    .as_ref()
+                    .is_none_or(|(_, smallest)| average < *smallest)
+                {
+                    picked = Some((tier, average));
+                }
+            }
+
+            start = end;
+        }
+
+        picked.map(|(tier, _)| tier)
+    }
+}
+
+// keeps every table in level 0
+impl CompactionStrategy for SizeTiered {
+    fn pick(&self, levels: &[Vec<TableSummary>]) -> Option<Compaction> {
+        let sizes: Vec<u64> = levels.first()?.iter().map(|t| t.file_size).collect();
+
+        Some(Compaction {
+            level: 0,
+            tables: self.tier(&sizes)?.collect(),
+            output_level: 0,
+            max_table_size: None,
+        })
+    }
+}
+
+// Leveled compaction: once level 0 holds enough tables they are all merged into level 1,
+// and once a deeper level grows past its size one of its tables is merged into the next
+// level. Only the tables of the next level overlapping the merged key range are
+// rewritten. Every level holds size_multiplier times more than the one above it, so
+// most of the data sits in the deepest level, and a read looks at a single table per
+// level below level 0.
+pub struct Leveled {
+    pub level0_tables: usize, // flushed tables that trigger a compaction into level 1
+    pub level1_size: u64,     // bytes level 1 holds before it is compacted
+    pub size_multiplier: u64, // growth of the size of every next level
+    pub table_size: u64,      // bytes of the tables written below level 0
+    pub levels: usize,        // the deepest level is never compacted
+}
+
+impl Default for Leveled {
+    fn default() -> Leveled {
+        Leveled {
+            level0_tables: 4,
+            level1_size: 10 * 1024 * 1024,
+            size_multiplier: 10,
+            table_size: 2 * 1024 * 1024,
+            levels: 7,
+        }
+    }
+}
+
+impl Leveled {
+    // how full a level is, a level at 1 or more needs to be compacted
+    fn score(&self, level: usize, tables: &[TableSummary]) -> f64 {
+        if level == 0 {
+            return tables.len() as f64 / self.level0_tables as f64;
+        }
+
+        let capacity =
+            self.level1_size as f64 * (self.size_multiplier as f64).powi(level as i32 - 1);
+        let size: u64 = tables.iter().map(|t| t.file_size).sum();
+
+        size as f64 / capacity
+    }
+}
+
+impl CompactionStrategy for Leveled {
+    fn pick(&self, levels: &[Vec<TableSummary>]) -> Option<Compaction> {
+        let last = usize::min(levels.len(), self.levels.saturating_sub(1));
+        let mut picked: Option<(usize, f64)> = None;
+
+        for (level, tables) in levels[..last].iter().enumerate() {
+            let score = self.score(level, tables);
+
+            if score >= 1.0 && picked.is_none_or(|(_, best)| score > best) {
+                picked = Some((level, score));
+            }
+        }
+
+        let (level, _) = picked?;
+
+        // the oldest table of the level has waited the longest for its turn
+        let tables = if level == 0 {
+            (0..levels[0].len()).collect()
+        } else {
+            let (oldest, _) = levels[level]
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, t)| t.number)?;
+            vec![oldest]
+        };
+
+        Some(Compaction {
+            level,
+            tables,
+            output_level: level + 1,
+            max_table_size: Some(self.table_size),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn size_tiered(min_tables: usize, max_tables: usize) -> SizeTiered {
+        SizeTiered {
+            min_tables,
+            max_tables,
+            bucket_ratio: 1.5,
+        }
+    }
+
+    #[test]
+    fn picks_nothing_until_a_tier_is_full() {
+        let strategy = size_tiered(4, 32);
+
+        assert_eq!(strategy.tier(&[]), None);
+        assert_eq!(strategy.tier(&[100, 110, 90]), None);
+        assert_eq!(strategy.tier(&[100, 1000, 110, 10_000]), None);
+        assert_eq!(strategy.tier(&[100, 110, 90, 105]), Some(0..4));
+    }
+
+    #[test]
+    fn picks_the_tier_with_the_smallest_tables() {
+        let strategy = size_tiered(2, 32);
+
+        let sizes = [100, 120, 1000, 1100, 900, 10_000];
+
+        assert_eq!(strategy.tier(&sizes), Some(0..2));
+        assert_eq!(strategy.tier(&sizes[2..]), Some(0..3));
+    }
+
+    #[test]
+    fn limits_the_tier_to_its_oldest_tables() {
+        let strategy = size_tiered(2, 3);
+
+        assert_eq!(strategy.tier(&[100, 100, 100, 100, 100, 5000]), Some(2..5));
+    }
+
+    fn summaries(sizes: &[u64]) -> Vec<TableSummary> {
+        sizes
+            .iter()
+            .enumerate()
+            .map(|(number, &file_size)| TableSummary {
+                number: number as u64,
+                file_size,
+            })
+            .collect()
+    }
+
+    fn leveled() -> Leveled {
+        Leveled {
+            level0_tables: 2,
+            level1_size: 1000,
+            size_multiplier: 10,
+            table_size: 100,
+            levels: 3,
+        }
+    }
+
+    #[test]
+    fn size_tiered_merges_level_0_in_place() {
+        let strategy = size_tiered(2, 32);
+
+        assert_eq!(strategy.pick(&[]), None);
+        assert_eq!(
+            strategy.pick(&[summaries(&[100, 1000, 1100])]),
+            Some(Compaction {
+                level: 0,
+                tables: vec![1, 2],
+                output_level: 0,
+                max_table_size: None,
+            })
+        );
+    }
+
+    #[test]
+    fn leveled_moves_every_level_0_table_down_once_enough_are_flushed() {
+        let strategy = leveled();
+
+        assert_eq!(strategy.pick(&[summaries(&[100])]), None);
+        assert_eq!(
+            strategy.pick(&[summaries(&[100, 100])]),
+            Some(Compaction {
+                level: 0,
+                tables: vec![0, 1],
+                output_level: 1,
+                max_table_size: Some(100),
+            })
+        );
+    }
+
+    #[test]
+    fn leveled_picks_the_oldest_table_of_the_fullest_level() {
+        let strategy = leveled();
+
+        let levels = [
+            summaries(&[100]),
+            vec![
+                TableSummary {
+                    number: 7,
+                    file_size: 500,
+                },
+                TableSummary {
+                    number: 3,
+                    file_size: 600,
+                },
+                TableSummary {
+                    number: 5,
+                    file_size: 500,
+                },
+            ],
+            summaries(&[4000]),
+        ];
+
+        assert_eq!(
+            strategy.pick(&levels),
+            Some(Compaction {
+                level: 1,
+                tables: vec![1],
+                output_level: 2,
+                max_table_size: Some(100),
+            })
+        );
+    }
+
+    #[test]
+    fn leveled_never_compacts_the_deepest_level() {
+        let strategy = leveled();
+
+        let levels = [
+            summaries(&[]),
+            summaries(&[100]),
+            summaries(&[20_000, 20_000]),
+        ];
+
+        assert_eq!(strategy.pick(&levels), None);
+    }
+}
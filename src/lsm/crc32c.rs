@@ -0,0 +1,60 @@
+// CRC-32C (Castagnoli), the checksum used by every record written to disk
+const POLYNOMIAL: u32 = 0x82F6_3B78;
+
+const TABLE: [u32; 256] = build_table();
+
+const fn build_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLYNOMIAL
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+
+        table[i] = crc;
+        i += 1;
+    }
+
+    table
+}
+
+pub fn checksum(data: &[u8]) -> u32 {
+    extend(0, data)
+}
+
+// continues a checksum previously computed over a prefix of the data
+pub fn extend(crc: u32, data: &[u8]) -> u32 {
+    let mut crc = !crc;
+
+    for byte in data {
+        crc = TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8);
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_matches_known_values() {
+        assert_eq!(checksum(b""), 0);
+        assert_eq!(checksum(b"123456789"), 0xE306_9283);
+        assert_eq!(checksum(&[0u8; 32]), 0x8A91_36AA);
+    }
+
+    #[test]
+    fn extend_is_equivalent_to_checksum_of_concatenation() {
+        assert_eq!(extend(checksum(b"1234"), b"56789"), checksum(b"123456789"));
+    }
+}
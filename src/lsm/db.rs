@@ -0,0 +1,2050 @@
+use crate::error::{Error, Result};
+use crate::lsm::append_only::AppendOnly;
+use crate::lsm::batch::WriteBatch;
+use crate::lsm::cache::{BlockCache, CacheStats};
+use crate::lsm::codec::Codec;
+use crate::lsm::compaction::{Compaction, CompactionStrategy, SizeTiered, TableSummary};
+use crate::lsm::filename::{file_path, parse_file_name, FileKind};
+use crate::lsm::internal_key::{self, InternalKey};
+use crate::lsm::manifest::{Manifest, TableFile, Version, VersionEdit};
+use crate::lsm::memtable::MemTable;
+use crate::lsm::merge::{MergingIterator, Source};
+use crate::lsm::merge_operator::{self, MergeOperator};
+use crate::lsm::record::Record;
+use crate::lsm::snapshot::{Retention, Snapshot, SnapshotList};
+use crate::lsm::sstable::reader::{FilterStats, Table};
+use crate::lsm::sstable::writer::TableWriter;
+use crate::lsm::sstable::TableOptions;
+use crate::lsm::temp_dir::TempDir;
+use crate::lsm::transaction::Transaction;
+use crate::symbol_table::{self, ST};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs;
+use std::ops::{Bound, Range, RangeBounds};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+pub struct Options {
+    pub memtable_size: usize, // bytes written to the memtable before it is rotated
+    pub compaction: Box<dyn CompactionStrategy>,
+    pub table: TableOptions,
+    pub block_cache_size: usize, // bytes of table blocks kept in memory
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            memtable_size: 4 * 1024 * 1024,
+            compaction: Box::new(SizeTiered::default()),
+            table: TableOptions::default(),
+            block_cache_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+const READ_ERROR: &str = "could not read from database";
+const WRITE_ERROR: &str = "could not write to database";
+
+// bytes of edits after which the manifest is rewritten as a single snapshot
+const MAX_MANIFEST_SIZE: usize = 1024 * 1024;
+
+type Bounds<KEY> = (Bound<KEY>, Bound<KEY>);
+
+// what flushes and compactions write: versions of keys along with their records
+type VersionTable<KEY, VALUE> = Table<InternalKey<KEY>, Record<VALUE>>;
+
+// sorted versions of a single source, before the merge gives out source sequences
+type Records<'a, KEY, VALUE> =
+    Box<dyn Iterator<Item = Result<(InternalKey<KEY>, Record<VALUE>)>> + 'a>;
+
+fn file_number(path: &Path) -> u64 {
+    parse_file_name(path).map_or(0, |(number, _)| number)
+}
+
+// Log-structured merge tree: writes go to a WAL-backed memtable, which is rotated to an
+// immutable memtable once it grows past Options::memtable_size and then flushed to a
+// level 0 table file. Options::compaction merges tables as they pile up. Reads consult
+// the memtables first and then the tables, newest to oldest.
+//
+// The manifest records which tables are live and at which level, anything else found
+// in the directory is left over from an interrupted flush or compaction.
+//
+// Every write gets the next sequence and adds a version of its key, reads see the newest
+// version written at or before their sequence: the last one, or that of a snapshot.
+// Flushes and compactions only keep the versions some read may still see.
+//
+// ST methods can't report I/O failures and panic on them.
+pub struct LsmDb<KEY, VALUE>
+where
+    KEY: Ord,
+{
+    dir: PathBuf,
+    options: Options,
+    manifest: Manifest,
+    memtable: MemTable<KEY, VALUE>,
+    immutable: Option<MemTable<KEY, VALUE>>,
+    levels: Vec<Vec<VersionTable<KEY, VALUE>>>, // level 0 newest first, deeper levels by key
+    cache: Arc<BlockCache>,                     // blocks of every table
+    next_file_number: u64,
+    last_sequence: u64, // of the newest write
+    snapshots: SnapshotList,
+    merge_operator: Option<Box<dyn MergeOperator<VALUE>>>,
+    retired_filter_stats: FilterStats, // of the tables compactions have removed
+    size: Cell<Option<usize>>,         // live keys, counted on demand and kept by writes
+    keys: AppendOnly<KEY>,
+    values: AppendOnly<VALUE>,
+    entries: AppendOnly<(KEY, VALUE)>,
+    temp_dir: Option<TempDir>,
+}
+
+impl<KEY, VALUE> LsmDb<KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    pub fn open(dir: &Path, options: Options) -> Result<LsmDb<KEY, VALUE>> {
+        if options.memtable_size == 0 {
+            return Err(Error::InvalidArgument(String::from(
+                "memtable_size must be greater than zero",
+            )));
+        }
+
+        fs::create_dir_all(dir)?;
+
+        // tables the version does not list are only removed once a whole manifest names
+        // every live table, never when the last edit was torn or CURRENT is missing
+        let (version, is_whole) = match Manifest::recover(dir)? {
+            Some(recovered) => (recovered.version, !recovered.is_torn),
+            None => (Version::default(), false),
+        };
+        let live: HashSet<u64> = version.tables().map(|table| table.number).collect();
+
+        let mut logs = Vec::new();
+        let mut obsolete = Vec::new();
+        let mut next_file_number = u64::max(version.next_file_number, 1);
+
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            let (number, kind) = match parse_file_name(&path) {
+                Some(parsed) => parsed,
+                None => continue,
+            };
+
+            match kind {
+                FileKind::Log if number >= version.log_number => logs.push(number),
+                FileKind::Table if live.contains(&number) || !is_whole => {}
+                // flushed logs, tables of unfinished flushes and compactions, manifests
+                // replaced by the one written below
+                _ => obsolete.push(path),
+            }
+
+            next_file_number = u64::max(next_file_number, number + 1);
+        }
+
+        let cache = BlockCache::new(options.block_cache_size);
+        let mut levels = Vec::new();
+
+        for files in &version.levels {
+            let tables = files
+                .iter()
+                .map(|file| Table::open(&file_path(dir, file.number, FileKind::Table), &cache))
+                .collect::<Result<Vec<VersionTable<KEY, VALUE>>>>()?;
+            levels.push(tables);
+        }
+
+        if levels.is_empty() {
+            levels.push(Vec::new());
+        }
+
+        levels[0].sort_unstable_by_key(|table| {
+            let sequence = version
+                .table(file_number(table.path()))
+                .map(|file| file.sequence);
+            std::cmp::Reverse(sequence)
+        });
+
+        for level in &mut levels[1..] {
+            level.sort_unstable_by(|a, b| a.key_range().cmp(&b.key_range()));
+        }
+
+        let manifest_number = next_file_number;
+        next_file_number += 1;
+
+        let manifest = Manifest::create(
+            dir,
+            manifest_number,
+            Version {
+                next_file_number,
+                ..version
+            },
+        )?;
+
+        for path in obsolete {
+            fs::remove_file(path)?;
+        }
+
+        logs.sort_unstable();
+
+        let current_log = logs.pop().unwrap_or_else(|| {
+            next_file_number += 1;
+            next_file_number - 1
+        });
+        let memtable = MemTable::open(&file_path(dir, current_log, FileKind::Log))?;
+        let last_sequence = u64::max(manifest.version().last_sequence, memtable.last_sequence());
+
+        let mut db = LsmDb {
+            dir: dir.to_path_buf(),
+            options,
+            manifest,
+            memtable,
+            immutable: None,
+            levels,
+            cache,
+            next_file_number,
+            last_sequence,
+            snapshots: SnapshotList::new(),
+            merge_operator: None,
+            retired_filter_stats: FilterStats::default(),
+            size: Cell::new(None),
+            keys: AppendOnly::new(),
+            values: AppendOnly::new(),
+            entries: AppendOnly::new(),
+            temp_dir: None,
+        };
+
+        // logs of rotated memtables that were not flushed before the process stopped
+        for number in logs {
+            let immutable = MemTable::open(&file_path(dir, number, FileKind::Log))?;
+            db.last_sequence = u64::max(db.last_sequence, immutable.last_sequence());
+            db.immutable = Some(immutable);
+            db.flush_immutable()?;
+        }
+
+        Ok(db)
+    }
+
+    // database in a new temporary directory, removed when the database is dropped
+    pub fn temporary(options: Options) -> Result<LsmDb<KEY, VALUE>> {
+        let temp_dir = TempDir::new("db")?;
+        let mut db = LsmDb::open(temp_dir.path(), options)?;
+        db.temp_dir = Some(temp_dir);
+
+        Ok(db)
+    }
+
+    #[cfg(test)]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    #[cfg(test)]
+    pub fn table_count(&self) -> usize {
+        self.levels.iter().map(Vec::len).sum()
+    }
+
+    // Bloom filter checks of every point lookup that reached the tables
+    pub fn filter_stats(&self) -> FilterStats {
+        let mut stats = self.retired_filter_stats;
+
+        for table in self.levels.iter().flatten() {
+            stats += table.filter_stats();
+        }
+
+        stats
+    }
+
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.stats()
+    }
+
+    // view of the database as of now, which later writes do not change
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshots.acquire(self.last_sequence)
+    }
+
+    pub fn get_at(&self, key: &KEY, snapshot: &Snapshot) -> Result<Option<VALUE>> {
+        let value = self.get_record(key, snapshot.sequence())?;
+        Ok(value.map(Cow::into_owned))
+    }
+
+    // entries in range the snapshot sees, in ascending key order
+    pub fn range_at<R: RangeBounds<KEY>>(
+        &self,
+        range: R,
+        snapshot: &Snapshot,
+    ) -> impl Iterator<Item = Result<(KEY, VALUE)>> + '_ {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.live(bounds, snapshot.sequence(), false)
+    }
+
+    // applies every write of the batch, or none of them when the process stops midway
+    pub fn write(&mut self, batch: WriteBatch<KEY, VALUE>) -> Result<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        // keys may repeat within a batch, so it leaves the live keys to be counted again
+        self.invalidate_reads();
+        self.size.set(None);
+        let sequence = self.last_sequence + 1;
+        self.last_sequence += batch.len() as u64;
+        self.memtable.write(sequence, batch)?;
+        self.rotate_if_full()
+    }
+
+    // operator folding the operands of merge, which reads of keys holding operands and
+    // compactions folding them need
+    pub fn set_merge_operator(&mut self, operator: impl MergeOperator<VALUE> + 'static) {
+        self.invalidate_reads();
+        self.merge_operator = Some(Box::new(operator));
+    }
+
+    // writes an operand the merge operator folds into the value of the key once it is
+    // read or compacted, which spares reading the value to update it
+    pub fn merge(&mut self, key: KEY, operand: VALUE) -> Result<()> {
+        if self.merge_operator.is_none() {
+            return Err(Error::InvalidArgument(String::from(
+                "merge without a merge operator",
+            )));
+        }
+
+        self.invalidate_reads();
+        self.count_write(&key, true)?;
+        let sequence = self.next_sequence();
+        self.memtable.merge(sequence, key, operand)?;
+        self.rotate_if_full()
+    }
+
+    // transaction reading the database as of now
+    pub fn begin(&self) -> Transaction<KEY, VALUE> {
+        Transaction::new(self.snapshot())
+    }
+
+    // applies the writes of the transaction as a batch, unless a key it read was written
+    // after it began
+    pub fn commit(&mut self, transaction: Transaction<KEY, VALUE>) -> Result<()> {
+        for key in transaction.reads() {
+            let newest = self.version(key, internal_key::NEWEST)?;
+
+            if newest.is_some_and(|(sequence, _)| sequence > transaction.sequence()) {
+                return Err(Error::Conflict);
+            }
+        }
+
+        self.write(transaction.into_batch())
+    }
+
+    // Keeps the count of live keys, when there is one, across a write leaving key live or
+    // not. Whether it was live takes a point lookup, which writes skip until size is asked.
+    fn count_write(&self, key: &KEY, live: bool) -> Result<()> {
+        if let Some(size) = self.size.get() {
+            let version = self.version(key, self.last_sequence)?;
+            let was_live = version.is_some_and(|(_, record)| !matches!(*record, Record::Tombstone));
+            self.size
+                .set(Some(size + live as usize - was_live as usize));
+        }
+
+        Ok(())
+    }
+
+    fn next_sequence(&mut self) -> u64 {
+        self.last_sequence += 1;
+        self.last_sequence
+    }
+
+    fn next_file_number(&mut self) -> u64 {
+        self.next_file_number += 1;
+        self.next_file_number - 1
+    }
+
+    // references handed out by reads can't be alive once the content changes
+    fn invalidate_reads(&mut self) {
+        self.keys.clear();
+        self.values.clear();
+        self.entries.clear();
+    }
+
+    fn rotate_if_full(&mut self) -> Result<()> {
+        if self.memtable.size_in_bytes() >= self.options.memtable_size {
+            self.rotate()?;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.flush_immutable()?;
+
+        let number = self.next_file_number();
+        let memtable = MemTable::open(&file_path(&self.dir, number, FileKind::Log))?;
+        self.immutable = Some(std::mem::replace(&mut self.memtable, memtable));
+
+        Ok(())
+    }
+
+    fn flush_immutable(&mut self) -> Result<()> {
+        let immutable = match &self.immutable {
+            Some(immutable) => immutable,
+            None => return Ok(()),
+        };
+
+        let log_path = immutable.log_path().to_path_buf();
+        let log_number = file_number(&log_path);
+
+        let mut edit = VersionEdit {
+            log_number: Some(log_number + 1),
+            ..VersionEdit::default()
+        };
+
+        let everything = (Bound::Unbounded, Bound::Unbounded);
+        let snapshots = self.snapshots.sequences();
+        let outputs = write_tables(
+            &self.dir,
+            &self.options.table,
+            &mut self.next_file_number,
+            merge_newest_first(vec![memtable_records(immutable, &everything, false)], false),
+            &Retention::new(&snapshots, false, self.merge_operator.as_deref()),
+            None,
+        )?;
+
+        let mut tables = Vec::new();
+
+        for number in outputs {
+            let table = Table::open(&file_path(&self.dir, number, FileKind::Table), &self.cache)?;
+            edit.added.push((0, table_file(&table, log_number)));
+            tables.push(table);
+        }
+
+        // the log is only dropped once its table is live
+        self.log_edit(edit)?;
+        self.levels[0].splice(0..0, tables);
+        self.immutable = None;
+
+        fs::remove_file(log_path)?;
+        self.compact_levels()
+    }
+
+    // records the edit in the manifest, starting a new manifest once the current one has
+    // grown too large
+    fn log_edit(&mut self, mut edit: VersionEdit) -> Result<()> {
+        edit.next_file_number = Some(self.next_file_number);
+        edit.last_sequence = Some(self.last_sequence);
+        self.manifest.log_and_apply(edit)?;
+
+        if self.manifest.size() >= MAX_MANIFEST_SIZE {
+            let number = self.next_file_number();
+            let version = Version {
+                next_file_number: self.next_file_number,
+                last_sequence: self.last_sequence,
+                ..self.manifest.version().clone()
+            };
+
+            let previous = self.manifest.path();
+            self.manifest = Manifest::create(&self.dir, number, version)?;
+            fs::remove_file(previous)?;
+        }
+
+        Ok(())
+    }
+
+    // writes everything held in memory to table files
+    pub fn flush(&mut self) -> Result<()> {
+        self.invalidate_reads();
+        self.rotate()?;
+        self.flush_immutable()
+    }
+
+    // merges every table into a single one, in the deepest level holding tables
+    pub fn compact(&mut self) -> Result<()> {
+        self.invalidate_reads();
+
+        let output_level = match self.levels.iter().rposition(|level| !level.is_empty()) {
+            Some(level) => level,
+            None => return Ok(()),
+        };
+
+        let everything = (Bound::Unbounded, Bound::Unbounded);
+        let sources = table_sources(&self.levels, &everything, false);
+        let snapshots = self.snapshots.sequences();
+
+        // nothing older remains below the merged table, so tombstones have nothing left
+        // to shadow
+        let outputs = write_tables(
+            &self.dir,
+            &self.options.table,
+            &mut self.next_file_number,
+            merge_newest_first(sources, false),
+            &Retention::new(&snapshots, true, self.merge_operator.as_deref()),
+            None,
+        )?;
+
+        let inputs = self
+            .levels
+            .iter()
+            .enumerate()
+            .flat_map(|(level, tables)| (0..tables.len()).map(move |position| (level, position)))
+            .collect();
+
+        self.install(inputs, output_level, 0, outputs)
+    }
+
+    // runs the compactions picked by Options::compaction until it picks none
+    fn compact_levels(&mut self) -> Result<()> {
+        loop {
+            let summaries: Vec<Vec<TableSummary>> = self
+                .levels
+                .iter()
+                .map(|level| {
+                    level
+                        .iter()
+                        .map(|table| TableSummary {
+                            number: file_number(table.path()),
+                            file_size: table.file_size(),
+                        })
+                        .collect()
+                })
+                .collect();
+
+            match self.options.compaction.pick(&summaries) {
+                Some(compaction) if self.is_runnable(&compaction) => {
+                    self.run_compaction(compaction)?
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    // compactions breaking the order of the levels, or not making progress, are ignored
+    fn is_runnable(&self, compaction: &Compaction) -> bool {
+        let Compaction {
+            level,
+            tables,
+            output_level,
+            ..
+        } = compaction;
+
+        let (first, last) = match (tables.first(), tables.last()) {
+            (Some(&first), Some(&last)) => (first, last),
+            _ => return false,
+        };
+
+        let ascending = tables.windows(2).all(|pair| pair[0] < pair[1]);
+        let in_level = self
+            .levels
+            .get(*level)
+            .is_some_and(|tables| last < tables.len());
+
+        if !ascending || !in_level {
+            return false;
+        }
+
+        if output_level == level {
+            *level == 0 && tables.len() > 1 && last - first + 1 == tables.len()
+        } else {
+            *output_level == level + 1
+                && (*level > 0 || tables.len() == self.levels[0].len() - first)
+        }
+    }
+
+    fn run_compaction(&mut self, compaction: Compaction) -> Result<()> {
+        self.invalidate_reads();
+
+        let Compaction {
+            level,
+            tables,
+            output_level,
+            max_table_size,
+        } = compaction;
+
+        if output_level == level {
+            return self.merge_in_place(tables[0]..tables[tables.len() - 1] + 1);
+        }
+
+        if self.levels.len() <= output_level {
+            self.levels.resize_with(output_level + 1, Vec::new);
+        }
+
+        let overlapping = self.overlapping(output_level, &self.levels[level], &tables);
+
+        // nothing to merge with, the table just changes level
+        if tables.len() == 1 && overlapping.is_empty() {
+            let number = file_number(self.levels[level][tables[0]].path());
+            let file = self.manifest.version().table(number).cloned();
+
+            self.log_edit(VersionEdit {
+                added: file.into_iter().map(|file| (output_level, file)).collect(),
+                deleted: vec![(level, number)],
+                ..VersionEdit::default()
+            })?;
+
+            let table = self.levels[level].remove(tables[0]);
+            self.levels[output_level].insert(overlapping.start, table);
+
+            return Ok(());
+        }
+
+        // nothing older remains below the output level, so tombstones have nothing left
+        // to shadow
+        let drop_tombstones = self.levels[output_level + 1..].iter().all(Vec::is_empty);
+
+        let everything = (Bound::Unbounded, Bound::Unbounded);
+        let inputs = &self.levels[level];
+        let mut sources: Vec<Records<'_, KEY, VALUE>> = tables
+            .iter()
+            .map(|&position| {
+                level_records(std::slice::from_ref(&inputs[position]), &everything, false)
+            })
+            .collect();
+        sources.push(level_records(
+            &self.levels[output_level][overlapping.clone()],
+            &everything,
+            false,
+        ));
+
+        let snapshots = self.snapshots.sequences();
+        let outputs = write_tables(
+            &self.dir,
+            &self.options.table,
+            &mut self.next_file_number,
+            merge_newest_first(sources, false),
+            &Retention::new(&snapshots, drop_tombstones, self.merge_operator.as_deref()),
+            max_table_size,
+        )?;
+
+        let mut inputs: Vec<(usize, usize)> =
+            tables.iter().map(|&position| (level, position)).collect();
+
+        let start = overlapping.start;
+        inputs.extend(overlapping.map(|position| (output_level, position)));
+
+        self.install(inputs, output_level, start, outputs)
+    }
+
+    // positions of the tables of a level whose key ranges overlap the given tables
+    fn overlapping(
+        &self,
+        level: usize,
+        tables: &[VersionTable<KEY, VALUE>],
+        positions: &[usize],
+    ) -> Range<usize> {
+        let ranges = positions
+            .iter()
+            .filter_map(|&position| user_key_range(&tables[position]));
+        let smallest = ranges.clone().map(|(min, _)| min).min();
+        let greatest = ranges.map(|(_, max)| max).max();
+
+        let (smallest, greatest) = match (smallest, greatest) {
+            (Some(smallest), Some(greatest)) => (smallest, greatest),
+            _ => return 0..0,
+        };
+
+        let level = &self.levels[level];
+        let start =
+            level.partition_point(|t| user_key_range(t).is_some_and(|(_, max)| max < smallest));
+        let end =
+            level.partition_point(|t| user_key_range(t).is_some_and(|(min, _)| min <= greatest));
+
+        start..usize::max(start, end)
+    }
+
+    // merges adjacent level 0 tables into one, which takes their place
+    fn merge_in_place(&mut self, tables: Range<usize>) -> Result<()> {
+        // nothing older remains below tables reaching the oldest one, so their tombstones
+        // have nothing left to shadow
+        let drop_tombstones =
+            tables.end == self.levels[0].len() && self.levels[1..].iter().all(Vec::is_empty);
+
+        let everything = (Bound::Unbounded, Bound::Unbounded);
+        let sources = self.levels[0][tables.clone()]
+            .iter()
+            .map(|table| level_records(std::slice::from_ref(table), &everything, false))
+            .collect();
+
+        let snapshots = self.snapshots.sequences();
+        let outputs = write_tables(
+            &self.dir,
+            &self.options.table,
+            &mut self.next_file_number,
+            merge_newest_first(sources, false),
+            &Retention::new(&snapshots, drop_tombstones, self.merge_operator.as_deref()),
+            None,
+        )?;
+
+        let start = tables.start;
+        let inputs = tables.map(|position| (0, position)).collect();
+
+        self.install(inputs, 0, start, outputs)
+    }
+
+    // makes the outputs of a compaction live in place of its inputs, given by level and
+    // position, with the outputs taking position in the output level once the inputs are
+    // gone. Levels and files only change once the manifest holds the edit.
+    fn install(
+        &mut self,
+        mut inputs: Vec<(usize, usize)>,
+        output_level: usize,
+        position: usize,
+        outputs: Vec<u64>,
+    ) -> Result<()> {
+        let mut edit = VersionEdit::default();
+
+        // merged tables hold writes as new as their newest input
+        let mut sequence = 0;
+
+        for &(level, input) in &inputs {
+            let number = file_number(self.levels[level][input].path());
+            let file = self.manifest.version().table(number);
+
+            sequence = u64::max(sequence, file.map_or(0, |file| file.sequence));
+            edit.deleted.push((level, number));
+        }
+
+        let mut tables = Vec::new();
+
+        for number in outputs {
+            let table = Table::open(&file_path(&self.dir, number, FileKind::Table), &self.cache)?;
+            edit.added
+                .push((output_level, table_file(&table, sequence)));
+            tables.push(table);
+        }
+
+        self.log_edit(edit)?;
+
+        // removing the last inputs first keeps the positions of the others
+        inputs.sort_unstable();
+        let mut paths = Vec::new();
+
+        for (level, input) in inputs.into_iter().rev() {
+            let table = self.levels[level].remove(input);
+            self.retired_filter_stats += table.filter_stats();
+            paths.push(table.path().to_path_buf());
+        }
+
+        self.levels[output_level].splice(position..position, tables);
+
+        for path in paths {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+
+    // memtables, newest first
+    fn memtables(&self) -> impl Iterator<Item = &MemTable<KEY, VALUE>> {
+        std::iter::once(&self.memtable).chain(self.immutable.iter())
+    }
+
+    // tables which may hold the key, newest first: every level 0 table, and the single
+    // table of every deeper level whose key range covers the key
+    fn candidate_tables<'a>(
+        &'a self,
+        key: &'a KEY,
+    ) -> impl Iterator<Item = &'a VersionTable<KEY, VALUE>> + 'a {
+        let deeper = self.levels[1..].iter().filter_map(move |level| {
+            let position =
+                level.partition_point(|t| user_key_range(t).is_some_and(|(_, max)| max < key));
+
+            level
+                .get(position)
+                .filter(|t| user_key_range(t).is_some_and(|(min, _)| min <= key))
+        });
+
+        self.levels[0].iter().chain(deeper)
+    }
+
+    // one source per memtable, level 0 table and deeper level, newest first
+    fn sources(&self, range: &Bounds<KEY>, reverse: bool) -> Vec<Records<'_, KEY, VALUE>> {
+        let mut sources: Vec<Records<'_, KEY, VALUE>> = self
+            .memtables()
+            .map(|memtable| memtable_records(memtable, range, reverse))
+            .collect();
+
+        sources.extend(table_sources(&self.levels, range, reverse));
+        sources
+    }
+
+    // value at sequence of every key in range, deleted keys left out
+    fn live(
+        &self,
+        range: Bounds<KEY>,
+        sequence: u64,
+        reverse: bool,
+    ) -> impl Iterator<Item = Result<(KEY, VALUE)>> + '_ {
+        let merged = merge_newest_first(self.sources(&range, reverse), reverse);
+        let operator = self.merge_operator.as_deref();
+
+        newest_versions(merged, sequence, reverse, operator).filter_map(|entry| match entry {
+            Ok((key, Some(value))) => Some(Ok((key, value))),
+            Ok((_, None)) => None,
+            Err(e) => Some(Err(e)),
+        })
+    }
+
+    // sequence and record of the newest version of the key at or before sequence
+    fn version(&self, key: &KEY, sequence: u64) -> Result<Option<(u64, Cow<'_, Record<VALUE>>)>> {
+        for memtable in self.memtables() {
+            if let Some((version, record)) = memtable.version(key, sequence) {
+                return Ok(Some((version.sequence, Cow::Borrowed(record))));
+            }
+        }
+
+        // the newest table holding a version decides, even when it is a tombstone
+        let lookup = InternalKey::lookup(key.clone(), sequence);
+
+        for table in self.candidate_tables(key) {
+            if let Some((version, record)) = table.get_entry(&lookup)? {
+                return Ok(Some((version.sequence, Cow::Owned(record))));
+            }
+        }
+
+        Ok(None)
+    }
+
+    // value of the key at sequence: that of its newest version unless it is a merge
+    // operand, which needs the older versions to be folded into
+    fn get_record(&self, key: &KEY, sequence: u64) -> Result<Option<Cow<'_, VALUE>>> {
+        let value = match self.version(key, sequence)? {
+            Some((_, record)) if matches!(*record, Record::Merge(_)) => {
+                let range = (Bound::Included(key.clone()), Bound::Included(key.clone()));
+                let merged = merge_newest_first(self.sources(&range, false), false);
+                let operator = self.merge_operator.as_deref();
+
+                let value = newest_versions(merged, sequence, false, operator).next();
+                value
+                    .transpose()?
+                    .and_then(|(_, value)| value)
+                    .map(Cow::Owned)
+            }
+            Some((_, Cow::Borrowed(record))) => record.value().map(Cow::Borrowed),
+            Some((_, Cow::Owned(record))) => record.into_value().map(Cow::Owned),
+            None => None,
+        };
+
+        Ok(value)
+    }
+
+    fn first_key(&self, range: Bounds<KEY>, reverse: bool) -> Result<Option<Cow<'_, KEY>>> {
+        let entry = self
+            .live(range, self.last_sequence, reverse)
+            .next()
+            .transpose()?;
+        Ok(entry.map(|(key, _)| Cow::Owned(key)))
+    }
+
+    fn entries(&self, range: Bounds<KEY>, reverse: bool) -> impl Iterator<Item = (&KEY, &VALUE)> {
+        self.live(range, self.last_sequence, reverse)
+            .map(move |entry| {
+                let entry = self.entries.alloc(entry.expect(READ_ERROR));
+                (&entry.0, &entry.1)
+            })
+    }
+}
+
+// one source per level 0 table and per deeper level, newest first
+fn table_sources<'a, KEY, VALUE>(
+    levels: &'a [Vec<VersionTable<KEY, VALUE>>],
+    range: &Bounds<KEY>,
+    reverse: bool,
+) -> Vec<Records<'a, KEY, VALUE>>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    let level0 = levels[0]
+        .iter()
+        .map(|table| level_records(std::slice::from_ref(table), range, reverse));
+    let deeper = levels[1..]
+        .iter()
+        .map(|level| level_records(level, range, reverse));
+
+    level0.chain(deeper).collect()
+}
+
+// versions in range held by a memtable
+fn memtable_records<'a, KEY, VALUE>(
+    memtable: &'a MemTable<KEY, VALUE>,
+    range: &Bounds<KEY>,
+    reverse: bool,
+) -> Records<'a, KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone,
+    VALUE: Codec + Clone,
+{
+    let tree = memtable.tree();
+    let range = internal_key::bounds(range);
+    let entries: Box<dyn Iterator<Item = (&InternalKey<KEY>, &Record<VALUE>)>> = if reverse {
+        Box::new(tree.range_rev(range))
+    } else {
+        Box::new(tree.range(range))
+    };
+
+    Box::new(entries.map(|(key, record)| Ok((key.clone(), record.clone()))))
+}
+
+// versions in range of tables sorted by key with non-overlapping key ranges
+fn level_records<'a, KEY, VALUE>(
+    tables: &'a [VersionTable<KEY, VALUE>],
+    range: &Bounds<KEY>,
+    reverse: bool,
+) -> Records<'a, KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    let range = internal_key::bounds(range);
+
+    if reverse {
+        Box::new(
+            tables
+                .iter()
+                .rev()
+                .flat_map(move |table| table.range_rev(range.clone())),
+        )
+    } else {
+        Box::new(
+            tables
+                .iter()
+                .flat_map(move |table| table.range(range.clone())),
+        )
+    }
+}
+
+// merges sources ordered newest first, newer sources getting greater sequences
+fn merge_newest_first<'a, KEY, VALUE>(
+    sources: Vec<Records<'a, KEY, VALUE>>,
+    reverse: bool,
+) -> MergingIterator<'a, InternalKey<KEY>, Record<VALUE>>
+where
+    KEY: Ord + 'a,
+    VALUE: 'a,
+{
+    let count = sources.len() as u64;
+
+    let sources = sources
+        .into_iter()
+        .enumerate()
+        .map(|(position, records)| {
+            let sequence = count - position as u64;
+            Box::new(records.map(move |record| record.map(|(key, value)| (key, value, sequence))))
+                as Source<'a, InternalKey<KEY>, Record<VALUE>>
+        })
+        .collect();
+
+    MergingIterator::new(sources, reverse)
+}
+
+// value at sequence of every user key of the merged versions, None when deleted: the
+// newest version written at or before sequence with the merge operands among them folded
+// into the older ones. Reverse merges give the versions of a user key oldest first.
+fn newest_versions<'a, KEY, VALUE>(
+    merged: MergingIterator<'a, InternalKey<KEY>, Record<VALUE>>,
+    sequence: u64,
+    reverse: bool,
+    operator: Option<&'a dyn MergeOperator<VALUE>>,
+) -> impl Iterator<Item = Result<(KEY, Option<VALUE>)>> + 'a
+where
+    KEY: Ord + 'a,
+    VALUE: 'a,
+{
+    let mut merged = merged.peekable();
+    let mut versions = Vec::new(); // visible records of the current user key
+
+    std::iter::from_fn(move || loop {
+        let (first, record, _) = match merged.next()? {
+            Ok(version) => version,
+            Err(e) => return Some(Err(e)),
+        };
+        let user_key = first.user_key;
+        versions.clear();
+
+        if first.sequence <= sequence {
+            versions.push(record);
+        }
+
+        while let Some(Ok((key, record, _))) =
+            merged.next_if(|version| matches!(version, Ok((key, _, _)) if key.user_key == user_key))
+        {
+            if key.sequence <= sequence {
+                versions.push(record);
+            }
+        }
+
+        if reverse {
+            versions.reverse();
+        }
+
+        let mut records = versions.drain(..);
+
+        if let Some(newest) = records.next() {
+            return match merge_operator::resolve(operator, newest, records) {
+                Ok(record) => Some(Ok((user_key, record.into_value()))),
+                Err(e) => Some(Err(e)),
+            };
+        }
+    })
+}
+
+// smallest and greatest user keys of a table
+fn user_key_range<KEY, VALUE>(table: &Table<InternalKey<KEY>, VALUE>) -> Option<(&KEY, &KEY)>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    let (min, max) = table.key_range()?;
+    Some((&min.user_key, &max.user_key))
+}
+
+// writes merged versions to new tables, keeping those retention asks for, and returns
+// their file numbers. A new table is started whenever one grows past max_table_size,
+// between two user keys so that a single table of a level holds every version of a key.
+fn write_tables<KEY, VALUE>(
+    dir: &Path,
+    options: &TableOptions,
+    next_file_number: &mut u64,
+    records: MergingIterator<'_, InternalKey<KEY>, Record<VALUE>>,
+    retention: &Retention<VALUE>,
+    max_table_size: Option<u64>,
+) -> Result<Vec<u64>>
+where
+    KEY: Codec + Ord,
+    VALUE: Codec,
+{
+    let mut numbers = Vec::new();
+    let mut writer: Option<TableWriter> = None;
+    let mut records = records.peekable();
+    let mut versions = Vec::new();
+
+    while let Some(record) = records.next() {
+        let (key, record, _) = record?;
+        versions.push((key, record));
+
+        // versions of a key are written together, once the last one is read
+        let more_versions = matches!(
+            records.peek(),
+            Some(Ok((next, _, _))) if next.user_key == versions[0].0.user_key
+        );
+
+        if more_versions {
+            continue;
+        }
+
+        retention.retain(&mut versions);
+
+        if versions.is_empty() {
+            continue;
+        }
+
+        let table = match &mut writer {
+            Some(table) => table,
+            None => {
+                numbers.push(*next_file_number);
+                let path = file_path(dir, *next_file_number, FileKind::Table);
+                *next_file_number += 1;
+
+                writer.insert(TableWriter::create(&path, options)?)
+            }
+        };
+
+        for (key, record) in versions.drain(..) {
+            table.add(&key, &record)?;
+        }
+
+        if max_table_size.is_some_and(|max| table.file_size() >= max) {
+            writer.take().unwrap().finish()?;
+        }
+    }
+
+    if let Some(table) = writer {
+        table.finish()?;
+    }
+
+    Ok(numbers)
+}
+
+// what the manifest records of a table
+fn table_file<KEY, VALUE>(table: &Table<KEY, VALUE>, sequence: u64) -> TableFile
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    let mut smallest = Vec::new();
+    let mut largest = Vec::new();
+
+    if let Some((min, max)) = table.key_range() {
+        min.encode(&mut smallest);
+        max.encode(&mut largest);
+    }
+
+    TableFile {
+        number: file_number(table.path()),
+        file_size: table.file_size(),
+        sequence,
+        smallest,
+        largest,
+    }
+}
+
+impl<KEY, VALUE> symbol_table::KvStore<KEY, VALUE> for LsmDb<KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    fn put(&mut self, key: KEY, value: VALUE) -> Result<()> {
+        self.invalidate_reads();
+        self.count_write(&key, true)?;
+        let sequence = self.next_sequence();
+        self.memtable.put(sequence, key, value)?;
+        self.rotate_if_full()
+    }
+
+    fn get(&self, key: &KEY) -> Result<Option<Cow<'_, VALUE>>> {
+        self.get_record(key, self.last_sequence)
+    }
+
+    // writes a tombstone, which hides the key from every older table
+    fn delete(&mut self, key: &KEY) -> Result<()> {
+        self.invalidate_reads();
+        self.count_write(key, false)?;
+        let sequence = self.next_sequence();
+        self.memtable.delete(sequence, key)?;
+        self.rotate_if_full()
+    }
+
+    fn size(&self) -> Result<usize> {
+        if let Some(size) = self.size.get() {
+            return Ok(size);
+        }
+
+        let size = self
+            .live(
+                (Bound::Unbounded, Bound::Unbounded),
+                self.last_sequence,
+                false,
+            )
+            .try_fold(0, |count, entry| entry.map(|_| count + 1))?;
+        self.size.set(Some(size));
+        Ok(size)
+    }
+
+    fn min(&self) -> Result<Option<Cow<'_, KEY>>> {
+        self.first_key((Bound::Unbounded, Bound::Unbounded), false)
+    }
+
+    fn max(&self) -> Result<Option<Cow<'_, KEY>>> {
+        self.first_key((Bound::Unbounded, Bound::Unbounded), true)
+    }
+
+    fn floor(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>> {
+        self.first_key((Bound::Unbounded, Bound::Included(key.clone())), true)
+    }
+
+    fn ceiling(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>> {
+        self.first_key((Bound::Included(key.clone()), Bound::Unbounded), false)
+    }
+
+    fn select(&self, position: usize) -> Result<Option<Cow<'_, KEY>>> {
+        if self.size.get().is_some_and(|size| position >= size) {
+            return Ok(None);
+        }
+
+        let entry = self
+            .live(
+                (Bound::Unbounded, Bound::Unbounded),
+                self.last_sequence,
+                false,
+            )
+            .nth(position)
+            .transpose()?;
+
+        Ok(entry.map(|(key, _)| Cow::Owned(key)))
+    }
+
+    // counts the keys up to key in one pass, the key itself coming last when it is live
+    fn rank(&self, key: &KEY) -> Result<Option<usize>> {
+        let mut rank = 0;
+        let mut found = false;
+
+        for entry in self.live(
+            (Bound::Unbounded, Bound::Included(key.clone())),
+            self.last_sequence,
+            false,
+        ) {
+            if entry?.0 == *key {
+                found = true;
+            } else {
+                rank += 1;
+            }
+        }
+
+        Ok(if found { Some(rank) } else { None })
+    }
+
+    fn scan<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = Result<(Cow<'a, KEY>, Cow<'a, VALUE>)>>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+
+        self.live(bounds, self.last_sequence, false)
+            .map(|entry| entry.map(|(key, value)| (Cow::Owned(key), Cow::Owned(value))))
+    }
+}
+
+impl<KEY, VALUE> ST<KEY, VALUE> for LsmDb<KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    fn new() -> LsmDb<KEY, VALUE> {
+        LsmDb::temporary(Options::default()).expect("could not create database")
+    }
+
+    fn put(&mut self, key: KEY, value: VALUE) {
+        symbol_table::KvStore::put(self, key, value).expect(WRITE_ERROR);
+    }
+
+    fn size(&self) -> usize {
+        symbol_table::KvStore::size(self).expect(READ_ERROR)
+    }
+
+    fn get(&self, key: &KEY) -> Option<&VALUE> {
+        let value = symbol_table::KvStore::get(self, key).expect(READ_ERROR)?;
+        Some(self.values.lend(value))
+    }
+
+    fn min(&self) -> Option<&KEY> {
+        let key = symbol_table::KvStore::min(self).expect(READ_ERROR)?;
+        Some(self.keys.lend(key))
+    }
+
+    fn max(&self) -> Option<&KEY> {
+        let key = symbol_table::KvStore::max(self).expect(READ_ERROR)?;
+        Some(self.keys.lend(key))
+    }
+
+    fn floor(&self, key: &KEY) -> Option<&KEY> {
+        let key = symbol_table::KvStore::floor(self, key).expect(READ_ERROR)?;
+        Some(self.keys.lend(key))
+    }
+
+    fn ceiling(&self, key: &KEY) -> Option<&KEY> {
+        let key = symbol_table::KvStore::ceiling(self, key).expect(READ_ERROR)?;
+        Some(self.keys.lend(key))
+    }
+
+    fn select(&self, position: usize) -> Option<&KEY> {
+        let key = symbol_table::KvStore::select(self, position).expect(READ_ERROR)?;
+        Some(self.keys.lend(key))
+    }
+
+    fn rank(&self, key: &KEY) -> Option<usize> {
+        symbol_table::KvStore::rank(self, key).expect(READ_ERROR)
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        self.entries((Bound::Unbounded, Bound::Unbounded), false)
+    }
+
+    fn range<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        let bounds = (range.start_bound().cloned(), range.end_bound().cloned());
+        self.entries(bounds, false)
+    }
+
+    fn rev<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        self.entries((Bound::Unbounded, Bound::Unbounded), true)
+    }
+
+    fn delete(&mut self, key: &KEY) {
+        symbol_table::KvStore::delete(self, key).expect(WRITE_ERROR);
+    }
+
+    fn delete_min(&mut self) {
+        if let Some(key) = self.min().cloned() {
+            self.delete(&key);
+        }
+    }
+
+    fn delete_max(&mut self) {
+        if let Some(key) = self.max().cloned() {
+            self.delete(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsm::compaction::Leveled;
+    use crate::lsm::filename::current_path;
+    use crate::lsm::manifest::Recovered;
+    use crate::lsm::merge_operator::U64Add;
+    use crate::symbol_table::red_black_bst::RedBlackBST;
+    use crate::symbol_table::test_client::symbol_table_integration::{run_store_tests, run_tests};
+    use crate::symbol_table::ReadOnlyST;
+
+    fn newest(key: &str) -> InternalKey<String> {
+        InternalKey::lookup(String::from(key), internal_key::NEWEST)
+    }
+
+    fn small_memtable() -> Options {
+        Options {
+            memtable_size: 64,
+            ..Options::default()
+        }
+    }
+
+    #[test]
+    fn run_integration_tests() {
+        run_tests::<LsmDb<String, String>>();
+    }
+
+    #[test]
+    fn run_store_tests_on_tables() {
+        run_store_tests(|| LsmDb::<String, String>::temporary(small_memtable()).unwrap());
+    }
+
+    #[test]
+    fn reads_merge_memtables_and_tables() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(small_memtable()).unwrap();
+        let reference = &mut RedBlackBST::<String, u64>::new();
+
+        // act
+        for i in 0..500_u64 {
+            let key = format!("key{:03}", (i * 7) % 300);
+            db.put(key.clone(), i);
+            reference.put(key, i);
+
+            if i % 5 == 0 {
+                let key = format!("key{:03}", (i * 3) % 300);
+                db.delete(&key);
+                reference.delete(&key);
+            }
+        }
+
+        // assert
+        assert!(db.table_count() > 1);
+        assert_eq!(db.size(), reference.size());
+        assert_eq!(db.min(), reference.min());
+        assert_eq!(db.max(), reference.max());
+
+        for i in 0..310 {
+            let key = format!("key{:03}", i);
+            let near = format!("key{:03}a", i);
+
+            assert_eq!(db.get(&key), reference.get(&key));
+            assert_eq!(db.floor(&near), reference.floor(&near));
+            assert_eq!(db.ceiling(&near), reference.ceiling(&near));
+            assert_eq!(db.rank(&key), reference.rank(&key));
+            assert_eq!(db.select(i), reference.select(i));
+        }
+
+        assert!(db.iter().eq(reference.iter()));
+        assert!(db.rev().eq(reference.rev()));
+    }
+
+    #[test]
+    fn counted_size_is_kept_by_later_writes() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(small_memtable()).unwrap();
+        db.set_merge_operator(U64Add);
+        let reference = &mut RedBlackBST::<String, u64>::new();
+
+        for i in 0..100_u64 {
+            let key = format!("key{:03}", i);
+            db.put(key.clone(), i);
+            reference.put(key, i);
+        }
+        assert_eq!(db.size(), reference.size());
+
+        // act
+        for i in 0..300_u64 {
+            let key = format!("key{:03}", (i * 7) % 150);
+
+            match i % 3 {
+                0 => {
+                    db.delete(&key);
+                    reference.delete(&key);
+                }
+                1 => {
+                    db.merge(key.clone(), 1).unwrap();
+                    let count = reference.get(&key).map_or(1, |count| count + 1);
+                    reference.put(key, count);
+                }
+                _ => {
+                    db.put(key.clone(), i);
+                    reference.put(key, i);
+                }
+            }
+        }
+
+        // assert
+        assert!(db.table_count() > 1);
+        assert_eq!(db.size.get(), Some(reference.size()));
+        assert_eq!(db.iter().count(), reference.size());
+        assert_eq!(db.select(reference.size()), None);
+
+        let mut batch = WriteBatch::new();
+        batch.delete(String::from("key000"));
+        db.write(batch).unwrap();
+        reference.delete(&String::from("key000"));
+        assert_eq!(db.size(), reference.size());
+    }
+
+    #[test]
+    fn tombstones_hide_flushed_keys_from_every_read() {
+        // arrange
+        let dir = TempDir::new("db-tombstones").unwrap();
+        let mut db = LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+
+        for (position, key) in "S E A R C H E X A M P L E".split(" ").enumerate() {
+            db.put(String::from(key), position as u64);
+        }
+        db.flush().unwrap();
+
+        // act
+        db.delete(&String::from("A"));
+        db.delete(&String::from("H"));
+        db.delete(&String::from("X"));
+
+        // assert
+        let assert_deleted = |db: &LsmDb<String, u64>| {
+            assert_eq!(db.size(), 7);
+            assert_eq!(db.get(&String::from("A")), None);
+            assert!(!db.contains(&String::from("H")));
+            assert_eq!(db.min(), Some(&String::from("C")));
+            assert_eq!(db.max(), Some(&String::from("S")));
+            assert_eq!(db.floor(&String::from("I")), Some(&String::from("E")));
+            assert_eq!(db.ceiling(&String::from("T")), None);
+            assert_eq!(db.select(2), Some(&String::from("L")));
+            assert_eq!(db.rank(&String::from("L")), Some(2));
+            assert_eq!(db.rank(&String::from("H")), None);
+            assert_eq!(
+                db.keys_in_range(&String::from("A"), &String::from("L")),
+                vec!["C", "E", "L"]
+            );
+        };
+
+        assert_deleted(&db);
+
+        db.flush().unwrap();
+        assert_eq!(db.table_count(), 2);
+        assert_deleted(&db);
+
+        drop(db);
+        let db = LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+        assert_deleted(&db);
+    }
+
+    #[test]
+    fn compaction_merges_tables_and_drops_tombstones() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(Options::default()).unwrap();
+
+        for i in 0..20_u64 {
+            db.put(format!("key{:02}", i), i);
+        }
+        db.flush().unwrap();
+
+        for i in 0..10_u64 {
+            db.put(format!("key{:02}", i), i * 100);
+            db.delete(&format!("key{:02}", i + 10));
+        }
+        db.flush().unwrap();
+
+        // act
+        db.compact().unwrap();
+
+        // assert
+        assert_eq!(db.table_count(), 1);
+        assert_eq!(db.levels[0][0].size(), 10);
+        assert_eq!(db.levels[0][0].get(&newest("key15")).unwrap(), None);
+        assert_eq!(db.size(), 10);
+        assert_eq!(db.get(&String::from("key05")), Some(&500));
+        assert_eq!(db.get(&String::from("key15")), None);
+    }
+
+    #[test]
+    fn size_tiered_compaction_merges_similar_tables() {
+        // arrange
+        let dir = TempDir::new("db-size-tiered").unwrap();
+        let options = || Options {
+            memtable_size: 64,
+            compaction: Box::new(SizeTiered {
+                min_tables: 2,
+                max_tables: 4,
+                bucket_ratio: 1.5,
+            }),
+            ..Options::default()
+        };
+        let db = &mut LsmDb::<String, u64>::open(dir.path(), options()).unwrap();
+        let reference = &mut RedBlackBST::<String, u64>::new();
+
+        // act
+        for i in 0..1000_u64 {
+            let key = format!("key{:03}", (i * 7) % 400);
+            db.put(key.clone(), i);
+            reference.put(key, i);
+
+            if i % 3 == 0 {
+                let key = format!("key{:03}", (i * 11) % 400);
+                db.delete(&key);
+                reference.delete(&key);
+            }
+        }
+
+        // assert
+        assert!(db.table_count() < 10);
+        assert!(db.iter().eq(reference.iter()));
+
+        let db = &mut LsmDb::<String, u64>::open(dir.path(), options()).unwrap();
+        assert!(db.iter().eq(reference.iter()));
+    }
+
+    #[test]
+    fn merged_tiers_keep_tombstones_above_older_tables() {
+        // arrange
+        let options = Options {
+            compaction: Box::new(SizeTiered {
+                min_tables: 2,
+                ..SizeTiered::default()
+            }),
+            ..Options::default()
+        };
+        let db = &mut LsmDb::<String, u64>::temporary(options).unwrap();
+
+        for i in 0..200_u64 {
+            db.put(format!("key{:03}", i), i);
+        }
+        db.flush().unwrap();
+
+        // act
+        db.delete(&String::from("key050"));
+        db.flush().unwrap();
+        db.put(String::from("key300"), 300);
+        db.flush().unwrap();
+
+        // assert
+        assert_eq!(db.table_count(), 2);
+        assert!(db.levels[0][0]
+            .get(&newest("key050"))
+            .unwrap()
+            .is_some_and(|record| record.is_tombstone()));
+        assert_eq!(db.get(&String::from("key050")), None);
+        assert_eq!(db.get(&String::from("key300")), Some(&300));
+        assert_eq!(db.size(), 200);
+    }
+
+    #[test]
+    fn leveled_compaction_keeps_deeper_levels_sorted_and_disjoint() {
+        // arrange
+        let dir = TempDir::new("db-leveled").unwrap();
+        let options = || Options {
+            memtable_size: 256,
+            compaction: Box::new(Leveled {
+                level0_tables: 2,
+                level1_size: 1024,
+                size_multiplier: 2,
+                table_size: 512,
+                levels: 4,
+            }),
+            ..Options::default()
+        };
+        let db = &mut LsmDb::<String, u64>::open(dir.path(), options()).unwrap();
+        let reference = &mut RedBlackBST::<String, u64>::new();
+
+        // act
+        for i in 0..2000_u64 {
+            let key = format!("key{:03}", (i * 7) % 500);
+            db.put(key.clone(), i);
+            reference.put(key, i);
+
+            if i % 3 == 0 {
+                let key = format!("key{:03}", (i * 11) % 500);
+                db.delete(&key);
+                reference.delete(&key);
+            }
+        }
+
+        // assert
+        let assert_levels = |db: &LsmDb<String, u64>| {
+            assert!(db.levels.len() > 2);
+            assert!(db.levels[0].len() < 2);
+
+            for level in &db.levels[1..] {
+                for pair in level.windows(2) {
+                    let (_, previous_max) = pair[0].key_range().unwrap();
+                    let (next_min, _) = pair[1].key_range().unwrap();
+
+                    assert!(previous_max < next_min);
+                }
+            }
+        };
+
+        assert_levels(db);
+        assert!(db.iter().eq(reference.iter()));
+
+        for i in 0..510 {
+            let key = format!("key{:03}", i);
+            assert_eq!(db.get(&key), reference.get(&key));
+        }
+
+        let db = &mut LsmDb::<String, u64>::open(dir.path(), options()).unwrap();
+        assert_levels(db);
+        assert!(db.iter().eq(reference.iter()));
+        assert!(db.rev().eq(reference.rev()));
+    }
+
+    #[test]
+    fn snapshots_read_the_versions_of_their_time() {
+        // arrange
+        let dir = TempDir::new("db-snapshots").unwrap();
+        let key = |i: u64| format!("key{:02}", i);
+
+        let mut db = LsmDb::<String, u64>::open(dir.path(), small_memtable()).unwrap();
+
+        for i in 0..50_u64 {
+            db.put(key(i), i);
+        }
+
+        // act
+        let snapshot = db.snapshot();
+
+        for i in 0..50_u64 {
+            db.put(key(i), i * 100);
+        }
+        db.delete(&key(7));
+        db.flush().unwrap();
+        db.compact().unwrap();
+
+        // assert
+        assert_eq!(db.get_at(&key(7), &snapshot).unwrap(), Some(7));
+        assert_eq!(db.get(&key(7)), None);
+        assert_eq!(db.get_at(&key(8), &snapshot).unwrap(), Some(8));
+        assert_eq!(db.get(&key(8)), Some(&800));
+
+        let seen: Vec<(String, u64)> = db.range_at(.., &snapshot).collect::<Result<_>>().unwrap();
+        assert_eq!(seen, (0..50).map(|i| (key(i), i)).collect::<Vec<_>>());
+        assert_eq!(db.range_at(key(45).., &snapshot).count(), 5);
+
+        // versions only the snapshot could read are dropped once it is
+        drop(snapshot);
+        db.compact().unwrap();
+
+        let versions: usize = db.levels.iter().flatten().map(Table::size).sum();
+        assert_eq!(versions, 49);
+
+        // later writes keep getting newer sequences after reopening
+        drop(db);
+        let mut db = LsmDb::<String, u64>::open(dir.path(), small_memtable()).unwrap();
+        let snapshot = db.snapshot();
+        db.put(key(0), 1);
+
+        assert_eq!(db.get_at(&key(0), &snapshot).unwrap(), Some(0));
+        assert_eq!(db.get(&key(0)), Some(&1));
+    }
+
+    #[test]
+    fn symbol_table_reads_see_user_keys_once() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(small_memtable()).unwrap();
+        let key = |i: u64| format!("key{:02}", i);
+        let mut snapshots = Vec::new();
+
+        // act
+        for round in 0..3_u64 {
+            for i in 0..20_u64 {
+                db.put(key(i), round * 100 + i);
+            }
+            snapshots.push(db.snapshot());
+        }
+        for i in 0..3 {
+            db.delete(&key(i));
+        }
+        db.flush().unwrap();
+        db.compact().unwrap();
+        db.put(key(10), 1);
+
+        // assert
+        let table = &db.levels.iter().flatten().next().unwrap();
+        let versions: Vec<InternalKey<String>> =
+            table.iter().map(|entry| entry.unwrap().0).collect();
+        assert_eq!(versions.len(), 63);
+        assert_eq!(versions.iter().filter(|key| key.is_deletion()).count(), 3);
+        assert!(versions.is_sorted());
+
+        assert_eq!(db.size(), 17);
+        assert_eq!(db.get(&key(3)), Some(&203));
+        assert_eq!(db.get(&key(10)), Some(&1));
+        assert_eq!(db.min(), Some(&key(3)));
+        assert_eq!(db.select(1), Some(&key(4)));
+        assert_eq!(db.rank(&key(10)), Some(7));
+        assert_eq!(
+            db.keys_in_range(&key(0), &key(5)),
+            vec![&key(3), &key(4), &key(5)]
+        );
+        assert!(db
+            .keys()
+            .into_iter()
+            .eq((3..20).map(key).collect::<Vec<_>>().iter()));
+    }
+
+    #[test]
+    fn write_batches_land_together() {
+        // arrange
+        let dir = TempDir::new("db-batch").unwrap();
+        let mut db = LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+        db.put(String::from("from"), 10);
+        db.put(String::from("gone"), 1);
+        let before = db.snapshot();
+
+        let mut batch = WriteBatch::new();
+        batch.put(String::from("from"), 7);
+        batch.put(String::from("to"), 3);
+        batch.delete(String::from("gone"));
+        batch.put(String::from("to"), 4);
+
+        // act
+        db.write(batch).unwrap();
+        db.write(WriteBatch::new()).unwrap();
+
+        // assert
+        let assert_moved = |db: &LsmDb<String, u64>| {
+            assert_eq!(db.size(), 2);
+            assert_eq!(db.get(&String::from("from")), Some(&7));
+            assert_eq!(db.get(&String::from("to")), Some(&4));
+            assert!(!db.contains(&String::from("gone")));
+        };
+
+        assert_moved(&db);
+        assert_eq!(db.get_at(&String::from("from"), &before).unwrap(), Some(10));
+        assert_eq!(db.get_at(&String::from("to"), &before).unwrap(), None);
+
+        drop(before);
+        drop(db);
+        let mut db = LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+        assert_moved(&db);
+
+        db.put(String::from("from"), 6);
+        assert_eq!(db.get(&String::from("from")), Some(&6));
+    }
+
+    #[test]
+    fn merge_operands_fold_on_reads_and_compactions() {
+        // arrange
+        let dir = TempDir::new("db-merge").unwrap();
+        let mut db = LsmDb::<String, u64>::open(dir.path(), small_memtable()).unwrap();
+        let key = |key: &str| String::from(key);
+
+        assert!(matches!(
+            db.merge(key("a"), 1),
+            Err(Error::InvalidArgument(_))
+        ));
+        db.set_merge_operator(U64Add);
+
+        db.put(key("a"), 10);
+        db.put(key("c"), 7);
+        let snapshot = db.snapshot();
+
+        // act
+        for _ in 0..50 {
+            db.merge(key("a"), 1).unwrap();
+            db.merge(key("b"), 2).unwrap();
+        }
+        db.delete(&key("c"));
+        db.merge(key("c"), 5).unwrap();
+
+        // assert
+        let assert_folded = |db: &LsmDb<String, u64>| {
+            assert_eq!(db.get(&key("a")), Some(&60));
+            assert_eq!(db.get(&key("b")), Some(&100));
+            assert_eq!(db.get(&key("c")), Some(&5));
+            assert_eq!(db.size(), 3);
+            assert!(db
+                .iter()
+                .eq([(&key("a"), &60), (&key("b"), &100), (&key("c"), &5)]));
+            assert_eq!(db.max(), Some(&key("c")));
+        };
+
+        assert!(db.table_count() > 0);
+        assert_folded(&db);
+        assert_eq!(db.get_at(&key("a"), &snapshot).unwrap(), Some(10));
+        assert_eq!(db.get_at(&key("b"), &snapshot).unwrap(), None);
+
+        // operands survive in the log until a reopened database has an operator again
+        drop(snapshot);
+        drop(db);
+        let mut db = LsmDb::<String, u64>::open(dir.path(), small_memtable()).unwrap();
+        assert!(symbol_table::KvStore::get(&db, &key("a")).is_err());
+
+        db.set_merge_operator(U64Add);
+        assert_folded(&db);
+
+        db.flush().unwrap();
+        db.compact().unwrap();
+        assert_folded(&db);
+
+        let versions: usize = db.levels.iter().flatten().map(Table::size).sum();
+        assert_eq!(versions, 3);
+    }
+
+    #[test]
+    fn filter_stats_survive_compaction() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(Options::default()).unwrap();
+
+        for i in 0..100_u64 {
+            db.put(format!("key{:03}", i), i);
+        }
+        db.flush().unwrap();
+
+        // act
+        for i in 0..200_u64 {
+            db.contains(&format!("key{:03}", i));
+        }
+        db.compact().unwrap();
+
+        // assert
+        let stats = db.filter_stats();
+
+        assert_eq!(stats.hits, 100);
+        assert_eq!(stats.misses + stats.false_positives, 100);
+    }
+
+    #[test]
+    fn reopened_database_recovers_tables_and_logs() {
+        // arrange
+        let dir = TempDir::new("db-reopen").unwrap();
+
+        {
+            let db = &mut LsmDb::<String, u64>::open(dir.path(), small_memtable()).unwrap();
+
+            for i in 0..100_u64 {
+                db.put(format!("key{:03}", i), i);
+            }
+        }
+
+        // act
+        let db = &mut LsmDb::<String, u64>::open(dir.path(), small_memtable()).unwrap();
+
+        // assert
+        assert_eq!(db.size(), 100);
+        assert_eq!(db.get(&String::from("key000")), Some(&0));
+        assert_eq!(db.get(&String::from("key099")), Some(&99));
+
+        db.flush().unwrap();
+        assert_eq!(db.get(&String::from("key099")), Some(&99));
+    }
+
+    #[test]
+    fn open_flushes_rotated_logs_and_removes_unfinished_tables() {
+        // arrange
+        let dir = TempDir::new("db-leftovers").unwrap();
+
+        {
+            let log = &mut MemTable::<String, u64>::open(&dir.path().join("000003.log")).unwrap();
+            log.put(1, String::from("older"), 1).unwrap();
+            log.put(2, String::from("shared"), 1).unwrap();
+
+            let log = &mut MemTable::<String, u64>::open(&dir.path().join("000005.log")).unwrap();
+            log.put(3, String::from("shared"), 2).unwrap();
+
+            fs::write(dir.path().join("000004.tmp"), b"half written table").unwrap();
+        }
+
+        // act
+        let db = LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+
+        // assert
+        assert_eq!(db.table_count(), 1);
+        assert_eq!(db.get(&String::from("older")), Some(&1));
+        assert_eq!(db.get(&String::from("shared")), Some(&2));
+        assert!(!dir.path().join("000003.log").exists());
+        assert!(!dir.path().join("000004.tmp").exists());
+    }
+
+    #[test]
+    fn open_removes_files_the_manifest_does_not_list() {
+        // arrange
+        let dir = TempDir::new("db-orphans").unwrap();
+
+        {
+            let db = &mut LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+            db.put(String::from("kept"), 1);
+            db.flush().unwrap();
+        }
+
+        // a table a crashed compaction did not get to record, and a stale manifest
+        fs::write(dir.path().join("000099.sst"), b"half written table").unwrap();
+        fs::write(dir.path().join("MANIFEST-000098"), b"").unwrap();
+
+        // act
+        let db = LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+
+        // assert
+        assert_eq!(db.table_count(), 1);
+        assert_eq!(db.get(&String::from("kept")), Some(&1));
+        assert!(!dir.path().join("000099.sst").exists());
+
+        let manifests: Vec<PathBuf> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| matches!(parse_file_name(path), Some((_, FileKind::Manifest))))
+            .collect();
+
+        assert_eq!(manifests, vec![db.manifest.path()]);
+    }
+
+    #[test]
+    fn open_keeps_tables_when_the_manifest_is_not_recovered_whole() {
+        // arrange
+        let dir = TempDir::new("db-damaged-manifest").unwrap();
+
+        {
+            let db = &mut LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+            db.put(String::from("a"), 1);
+            db.flush().unwrap();
+            db.put(String::from("b"), 2);
+            db.flush().unwrap();
+        }
+
+        let tables = || {
+            let mut tables: Vec<PathBuf> = fs::read_dir(dir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| matches!(parse_file_name(path), Some((_, FileKind::Table))))
+                .collect();
+            tables.sort();
+            tables
+        };
+        let written = tables();
+
+        let current = fs::read_to_string(current_path(dir.path())).unwrap();
+        let manifest = dir.path().join(current.trim_end());
+        let intact = fs::read(&manifest).unwrap();
+        let mut corrupted = intact.clone();
+        corrupted[8] ^= 0xff; // payload of the first edit
+
+        // act
+        fs::write(&manifest, &corrupted).unwrap();
+        let opened_corrupted = LsmDb::<String, u64>::open(dir.path(), Options::default());
+        let kept_corrupted = tables();
+
+        fs::write(&manifest, &intact[..intact.len() - 3]).unwrap();
+        let opened_torn = LsmDb::<String, u64>::open(dir.path(), Options::default());
+        let kept_torn = tables();
+
+        // assert
+        match opened_corrupted {
+            Err(Error::Corruption { file, offset: 0 }) => assert_eq!(file, manifest),
+            _ => panic!("corrupted manifest not reported"),
+        }
+        assert_eq!(written.len(), 2);
+        assert_eq!(kept_corrupted, written);
+
+        let db = opened_torn.unwrap();
+        assert_eq!(db.table_count(), 1);
+        assert_eq!(kept_torn, written);
+    }
+
+    #[test]
+    fn fallible_operations_report_errors_instead_of_panicking() {
+        // arrange
+        let dir = TempDir::new("db-fallible").unwrap();
+
+        {
+            let db = &mut LsmDb::<String, u64>::open(dir.path(), Options::default()).unwrap();
+            symbol_table::KvStore::put(db, String::from("a"), 1).unwrap();
+            symbol_table::KvStore::put(db, String::from("b"), 2).unwrap();
+            symbol_table::KvStore::delete(db, &String::from("a")).unwrap();
+            db.flush().unwrap();
+
+            let entries: Vec<(Cow<String>, Cow<u64>)> = symbol_table::KvStore::scan(&*db, ..)
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(
+                entries,
+                vec![(Cow::Owned(String::from("b")), Cow::Owned(2))]
+            );
+        }
+
+        let table = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .find(|path| matches!(parse_file_name(path), Some((_, FileKind::Table))))
+            .unwrap();
+        let mut bytes = fs::read(&table).unwrap();
+        bytes[0] ^= 0xff;
+        fs::write(&table, &bytes).unwrap();
+
+        // act
+        let reopened = LsmDb::<String, u64>::open(dir.path(), Options::default());
+        let invalid = LsmDb::<String, u64>::temporary(Options {
+            memtable_size: 0,
+            ..Options::default()
+        });
+
+        // assert
+        match reopened {
+            Err(Error::Corruption { file, offset: 0 }) => assert_eq!(file, table),
+            _ => panic!("corrupted table not reported"),
+        }
+        assert!(matches!(invalid, Err(Error::InvalidArgument(_))));
+    }
+
+    #[test]
+    fn manifest_is_rewritten_once_it_grows_too_large() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(small_memtable()).unwrap();
+        let first = file_number(&db.manifest.path());
+
+        // act
+        while file_number(&db.manifest.path()) == first {
+            db.put(String::from("key"), 1);
+        }
+
+        // assert
+        assert!(!file_path(db.dir(), first, FileKind::Manifest).exists());
+        assert!(db.manifest.size() < MAX_MANIFEST_SIZE);
+        assert_eq!(
+            Manifest::recover(db.dir()).unwrap(),
+            Some(Recovered {
+                number: file_number(&db.manifest.path()),
+                version: db.manifest.version().clone(),
+                is_torn: false,
+            })
+        );
+    }
+
+    #[test]
+    fn temporary_database_removes_its_directory() {
+        let db = LsmDb::<String, u64>::temporary(Options::default()).unwrap();
+        let dir = db.dir().to_path_buf();
+
+        assert!(dir.exists());
+        drop(db);
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,44 @@
+use std::path::{Path, PathBuf};
+
+// names of the files of a database directory, every file but CURRENT numbered from a
+// single counter
+pub enum FileKind {
+    Log,
+    Table,
+    Manifest,
+    Temporary,
+}
+
+pub fn file_path(dir: &Path, number: u64, kind: FileKind) -> PathBuf {
+    let name = match kind {
+        FileKind::Log => format!("{:06}.log", number),
+        FileKind::Table => format!("{:06}.sst", number),
+        FileKind::Manifest => format!("MANIFEST-{:06}", number),
+        FileKind::Temporary => format!("{:06}.tmp", number),
+    };
+
+    dir.join(name)
+}
+
+// names the manifest in use
+pub fn current_path(dir: &Path) -> PathBuf {
+    dir.join("CURRENT")
+}
+
+pub fn parse_file_name(path: &Path) -> Option<(u64, FileKind)> {
+    let name = path.file_name()?.to_str()?;
+
+    if let Some(number) = name.strip_prefix("MANIFEST-") {
+        return Some((number.parse().ok()?, FileKind::Manifest));
+    }
+
+    let (stem, extension) = name.split_once('.')?;
+    let number = stem.parse().ok()?;
+
+    match extension {
+        "log" => Some((number, FileKind::Log)),
+        "sst" => Some((number, FileKind::Table)),
+        "tmp" => Some((number, FileKind::Temporary)),
+        _ => None,
+    }
+}
@@ -0,0 +1,179 @@
+use crate::lsm::codec::{self, Codec};
+use std::cmp::Ordering;
+use std::ops::Bound;
+
+// sequences no write gets, older and newer than every version. Sequences share a u64
+// with the kind, which leaves them 56 bits.
+pub const OLDEST: u64 = 0;
+pub const NEWEST: u64 = (1 << 56) - 1;
+
+// what the write that produced a version did to its key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Deletion = 0,
+    Value = 1,
+    Merge = 2,
+}
+
+impl Kind {
+    fn from_u8(kind: u8) -> Option<Kind> {
+        match kind {
+            0 => Some(Kind::Deletion),
+            1 => Some(Kind::Value),
+            2 => Some(Kind::Merge),
+            _ => None,
+        }
+    }
+}
+
+// Version of a user key written by the write with the given sequence. Versions are
+// ordered by user key and, for the same user key, newest first, so a lookup at a
+// sequence finds the newest version it may see at or after (key, sequence).
+//
+// encoded as: | user key | sequence << 8 | kind (u64) |
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InternalKey<KEY> {
+    pub user_key: KEY,
+    pub sequence: u64,
+    pub kind: Kind,
+}
+
+impl<KEY> InternalKey<KEY> {
+    pub fn new(user_key: KEY, sequence: u64, kind: Kind) -> InternalKey<KEY> {
+        InternalKey {
+            user_key,
+            sequence,
+            kind,
+        }
+    }
+
+    // sorts before every version of the user key written at or before sequence
+    pub fn lookup(user_key: KEY, sequence: u64) -> InternalKey<KEY> {
+        InternalKey::new(user_key, sequence, Kind::Merge)
+    }
+
+    pub fn is_deletion(&self) -> bool {
+        self.kind == Kind::Deletion
+    }
+}
+
+impl<KEY: Ord> Ord for InternalKey<KEY> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.user_key
+            .cmp(&other.user_key)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+            .then_with(|| other.kind.cmp(&self.kind))
+    }
+}
+
+impl<KEY: Ord> PartialOrd for InternalKey<KEY> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<KEY: Codec> Codec for InternalKey<KEY> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        self.user_key.encode(buffer);
+        codec::put_u64(buffer, self.sequence << 8 | self.kind as u64);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<InternalKey<KEY>> {
+        let split = bytes.len().checked_sub(8)?;
+        let user_key = KEY::decode(&bytes[..split])?;
+        let trailer = codec::get_u64(bytes, split)?;
+        let kind = Kind::from_u8(trailer as u8)?;
+
+        Some(InternalKey::new(user_key, trailer >> 8, kind))
+    }
+
+    fn encoded_len(&self) -> usize {
+        self.user_key.encoded_len() + 8
+    }
+
+    // every version of a user key is the same key to filters and lookups
+    fn user_key(encoded: &[u8]) -> &[u8] {
+        &encoded[..encoded.len().saturating_sub(8)]
+    }
+}
+
+// sorts after every version of the user key
+fn oldest<KEY: Clone>(user_key: &KEY) -> InternalKey<KEY> {
+    InternalKey::new(user_key.clone(), OLDEST, Kind::Deletion)
+}
+
+// bounds of the versions of the user keys within the bounds
+pub fn bounds<KEY: Clone>(
+    range: &(Bound<KEY>, Bound<KEY>),
+) -> (Bound<InternalKey<KEY>>, Bound<InternalKey<KEY>>) {
+    let start = match &range.0 {
+        Bound::Included(key) => Bound::Included(InternalKey::lookup(key.clone(), NEWEST)),
+        Bound::Excluded(key) => Bound::Excluded(oldest(key)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+    let end = match &range.1 {
+        Bound::Included(key) => Bound::Included(oldest(key)),
+        Bound::Excluded(key) => Bound::Excluded(InternalKey::lookup(key.clone(), NEWEST)),
+        Bound::Unbounded => Bound::Unbounded,
+    };
+
+    (start, end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(user_key: &str, sequence: u64) -> InternalKey<String> {
+        InternalKey::new(String::from(user_key), sequence, Kind::Value)
+    }
+
+    #[test]
+    fn versions_are_ordered_newest_first() {
+        let mut keys = vec![key("b", 1), key("a", 1), key("a", 7), key("b", 3)];
+        keys.sort();
+
+        assert_eq!(
+            keys,
+            vec![key("a", 7), key("a", 1), key("b", 3), key("b", 1)]
+        );
+    }
+
+    #[test]
+    fn roundtrip_and_user_key() {
+        let buffer = &mut Vec::new();
+        key("word", 42).encode(buffer);
+
+        assert_eq!(buffer.len(), key("word", 42).encoded_len());
+        assert_eq!(InternalKey::decode(buffer), Some(key("word", 42)));
+        assert_eq!(InternalKey::<String>::user_key(buffer), b"word");
+        assert_eq!(InternalKey::<String>::decode(&[1, 2]), None);
+
+        let deletion = InternalKey::new(String::from("word"), NEWEST, Kind::Deletion);
+        let buffer = &mut Vec::new();
+        deletion.encode(buffer);
+        assert_eq!(InternalKey::decode(buffer), Some(deletion));
+
+        buffer[4] = 7;
+        assert_eq!(InternalKey::<String>::decode(buffer), None);
+    }
+
+    #[test]
+    fn bounds_cover_every_version() {
+        let range = (
+            Bound::Excluded(String::from("a")),
+            Bound::Included(String::from("c")),
+        );
+        let (start, end) = bounds(&range);
+        let within = |key: &InternalKey<String>| {
+            crate::symbol_table::is_after_start(&(start.clone(), end.clone()), key)
+                && crate::symbol_table::is_before_end(&(start.clone(), end.clone()), key)
+        };
+
+        assert!(!within(&key("a", OLDEST)));
+        assert!(!within(&key("a", NEWEST)));
+        assert!(within(&key("b", NEWEST)));
+        assert!(within(&key("c", OLDEST)));
+        assert!(!within(&key("d", NEWEST)));
+    }
+}
@@ -0,0 +1,504 @@
+use crate::error::{Error, Result};
+use crate::lsm::codec;
+use crate::lsm::filename::{current_path, file_path, FileKind};
+use crate::lsm::wal::WriteAheadLog;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const LOG_NUMBER: u8 = 1;
+const NEXT_FILE_NUMBER: u8 = 2;
+const ADDED: u8 = 3;
+const DELETED: u8 = 4;
+const LAST_SEQUENCE: u8 = 5;
+
+// a live table file. Keys are kept encoded, the manifest does not know their type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TableFile {
+    pub number: u64,
+    pub file_size: u64,
+    pub sequence: u64, // newest flush whose writes the table holds, orders level 0
+    pub smallest: Vec<u8>,
+    pub largest: Vec<u8>,
+}
+
+// change from one version to the next
+#[derive(Debug, Default, PartialEq)]
+pub struct VersionEdit {
+    pub log_number: Option<u64>,
+    pub next_file_number: Option<u64>,
+    pub last_sequence: Option<u64>,
+    pub added: Vec<(usize, TableFile)>, // level and table
+    pub deleted: Vec<(usize, u64)>,     // level and table number
+}
+
+impl VersionEdit {
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        if let Some(number) = self.log_number {
+            buffer.push(LOG_NUMBER);
+            codec::put_u64(buffer, number);
+        }
+
+        if let Some(number) = self.next_file_number {
+            buffer.push(NEXT_FILE_NUMBER);
+            codec::put_u64(buffer, number);
+        }
+
+        if let Some(sequence) = self.last_sequence {
+            buffer.push(LAST_SEQUENCE);
+            codec::put_u64(buffer, sequence);
+        }
+
+        for (level, table) in &self.added {
+            buffer.push(ADDED);
+            codec::put_u64(buffer, *level as u64);
+            codec::put_u64(buffer, table.number);
+            codec::put_u64(buffer, table.file_size);
+            codec::put_u64(buffer, table.sequence);
+            codec::put_length_prefixed(buffer, &table.smallest);
+            codec::put_length_prefixed(buffer, &table.largest);
+        }
+
+        for (level, number) in &self.deleted {
+            buffer.push(DELETED);
+            codec::put_u64(buffer, *level as u64);
+            codec::put_u64(buffer, *number);
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Option<VersionEdit> {
+        let mut edit = VersionEdit::default();
+        let mut offset = 0;
+
+        while offset < bytes.len() {
+            let tag = bytes[offset];
+            offset += 1;
+
+            match tag {
+                LOG_NUMBER => {
+                    edit.log_number = Some(codec::get_u64(bytes, offset)?);
+                    offset += 8;
+                }
+                NEXT_FILE_NUMBER => {
+                    edit.next_file_number = Some(codec::get_u64(bytes, offset)?);
+                    offset += 8;
+                }
+                LAST_SEQUENCE => {
+                    edit.last_sequence = Some(codec::get_u64(bytes, offset)?);
+                    offset += 8;
+                }
+                ADDED => {
+                    let level = codec::get_u64(bytes, offset)? as usize;
+                    let number = codec::get_u64(bytes, offset + 8)?;
+                    let file_size = codec::get_u64(bytes, offset + 16)?;
+                    let sequence = codec::get_u64(bytes, offset + 24)?;
+                    let (smallest, next) = codec::get_length_prefixed(bytes, offset + 32)?;
+                    let (largest, next) = codec::get_length_prefixed(bytes, next)?;
+
+                    let table = TableFile {
+                        number,
+                        file_size,
+                        sequence,
+                        smallest,
+                        largest,
+                    };
+                    edit.added.push((level, table));
+                    offset = next;
+                }
+                DELETED => {
+                    let level = codec::get_u64(bytes, offset)? as usize;
+                    let number = codec::get_u64(bytes, offset + 8)?;
+
+                    edit.deleted.push((level, number));
+                    offset += 16;
+                }
+                _ => return None,
+            }
+        }
+
+        Some(edit)
+    }
+}
+
+// the live files of the database
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Version {
+    pub log_number: u64, // logs numbered below it are flushed to tables
+    pub next_file_number: u64,
+    pub last_sequence: u64, // of the newest write tables or logs may hold
+    pub levels: Vec<Vec<TableFile>>,
+}
+
+impl Version {
+    pub fn apply(&mut self, edit: &VersionEdit) {
+        if let Some(number) = edit.log_number {
+            self.log_number = number;
+        }
+
+        if let Some(number) = edit.next_file_number {
+            self.next_file_number = number;
+        }
+
+        if let Some(sequence) = edit.last_sequence {
+            self.last_sequence = sequence;
+        }
+
+        for (level, number) in &edit.deleted {
+            if let Some(tables) = self.levels.get_mut(*level) {
+                tables.retain(|table| table.number != *number);
+            }
+        }
+
+        for (level, table) in &edit.added {
+            if self.levels.len() <= *level {
+                self.levels.resize_with(level + 1, Vec::new);
+            }
+
+            self.levels[*level].push(table.clone());
+        }
+    }
+
+    pub fn table(&self, number: u64) -> Option<&TableFile> {
+        self.levels
+            .iter()
+            .flatten()
+            .find(|table| table.number == number)
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &TableFile> {
+        self.levels.iter().flatten()
+    }
+
+    // single edit turning an empty version into this one
+    fn snapshot(&self) -> VersionEdit {
+        VersionEdit {
+            log_number: Some(self.log_number),
+            next_file_number: Some(self.next_file_number),
+            last_sequence: Some(self.last_sequence),
+            added: self
+                .levels
+                .iter()
+                .enumerate()
+                .flat_map(|(level, tables)| tables.iter().map(move |table| (level, table.clone())))
+                .collect(),
+            deleted: Vec::new(),
+        }
+    }
+}
+
+// version the current manifest holds
+#[derive(Debug, PartialEq)]
+pub struct Recovered {
+    pub number: u64, // of the manifest
+    pub version: Version,
+    pub is_torn: bool, // the last edit was cut short by a crash and is left out
+}
+
+// Log of version edits. The manifest in use is named by the CURRENT file, which is only
+// replaced once a new manifest holds the whole version, so that a crash leaves either
+// the old or the new manifest in effect.
+pub struct Manifest {
+    log: WriteAheadLog,
+    size: usize, // bytes appended so far
+    version: Version,
+}
+
+impl Manifest {
+    // version of the manifest CURRENT names, none for a new database. A bad edit with no
+    // intact one after it was torn by a crash and is left out, any other fails the
+    // recovery with Error::Corruption, see WriteAheadLog::replay_records.
+    pub fn recover(dir: &Path) -> Result<Option<Recovered>> {
+        let current = match fs::read_to_string(current_path(dir)) {
+            Ok(current) => current,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let number = current
+            .trim_end()
+            .strip_prefix("MANIFEST-")
+            .and_then(|number| number.parse().ok())
+            .ok_or_else(|| Error::corruption(current_path(dir), 0))?;
+
+        let path = file_path(dir, number, FileKind::Manifest);
+
+        if !path.exists() {
+            return Err(Error::NotFound(path));
+        }
+
+        let mut version = Version::default();
+
+        let is_torn =
+            WriteAheadLog::replay_records(&path, VersionEdit::decode, |edit| version.apply(&edit))?;
+
+        Ok(Some(Recovered {
+            number,
+            version,
+            is_torn,
+        }))
+    }
+
+    // writes the version to a new manifest and then makes it the current one
+    pub fn create(dir: &Path, number: u64, version: Version) -> Result<Manifest> {
+        let path = file_path(dir, number, FileKind::Manifest);
+        let mut log = WriteAheadLog::open(&path)?;
+
+        let mut snapshot = Vec::new();
+        version.snapshot().encode(&mut snapshot);
+        log.append(&snapshot)?;
+        log.sync()?;
+
+        let temporary = file_path(dir, number, FileKind::Temporary);
+        let mut current = File::create(&temporary)?;
+        writeln!(current, "MANIFEST-{:06}", number)?;
+        current.sync_all()?;
+
+        fs::rename(&temporary, current_path(dir))?;
+        File::open(dir)?.sync_all()?;
+
+        Ok(Manifest {
+            log,
+            size: snapshot.len(),
+            version,
+        })
+    }
+
+    pub fn path(&self) -> PathBuf {
+        self.log.path().to_path_buf()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn version(&self) -> &Version {
+        &self.version
+    }
+
+    // an edit is only applied once it is durable, an empty one changes nothing and is
+    // not logged
+    pub fn log_and_apply(&mut self, edit: VersionEdit) -> Result<()> {
+        let mut payload = Vec::new();
+        edit.encode(&mut payload);
+
+        if payload.is_empty() {
+            return Ok(());
+        }
+
+        self.log.append(&payload)?;
+        self.log.sync()?;
+
+        self.size += payload.len();
+        self.version.apply(&edit);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsm::temp_dir::TempDir;
+
+    fn table(number: u64) -> TableFile {
+        TableFile {
+            number,
+            file_size: number * 100,
+            sequence: number,
+            smallest: format!("a{}", number).into_bytes(),
+            largest: format!("z{}", number).into_bytes(),
+        }
+    }
+
+    #[test]
+    fn version_edit_roundtrip() {
+        let edit = VersionEdit {
+            log_number: Some(7),
+            next_file_number: Some(12),
+            last_sequence: Some(40),
+            added: vec![(0, table(9)), (2, table(11))],
+            deleted: vec![(1, 3), (1, 4)],
+        };
+
+        let mut buffer = Vec::new();
+        edit.encode(&mut buffer);
+
+        assert_eq!(VersionEdit::decode(&buffer), Some(edit));
+        assert_eq!(VersionEdit::decode(&buffer[..buffer.len() - 1]), None);
+    }
+
+    #[test]
+    fn recover_replays_edits_on_top_of_the_snapshot() {
+        // arrange
+        let dir = TempDir::new("manifest-recover").unwrap();
+        let mut version = Version::default();
+        version.apply(&VersionEdit {
+            added: vec![(0, table(2)), (0, table(3))],
+            ..VersionEdit::default()
+        });
+
+        // act
+        let manifest = &mut Manifest::create(dir.path(), 4, version).unwrap();
+        manifest
+            .log_and_apply(VersionEdit {
+                log_number: Some(5),
+                next_file_number: Some(7),
+                last_sequence: Some(30),
+                added: vec![(1, table(6))],
+                deleted: vec![(0, 2), (0, 3)],
+            })
+            .unwrap();
+
+        // assert
+        let recovered = Manifest::recover(dir.path()).unwrap().unwrap();
+
+        assert_eq!(recovered.number, 4);
+        assert!(!recovered.is_torn);
+        assert_eq!(&recovered.version, manifest.version());
+        let recovered = recovered.version;
+        assert_eq!(recovered.log_number, 5);
+        assert_eq!(recovered.next_file_number, 7);
+        assert_eq!(recovered.last_sequence, 30);
+        assert_eq!(recovered.levels, vec![vec![], vec![table(6)]]);
+    }
+
+    #[test]
+    fn recover_ignores_a_torn_edit() {
+        // arrange
+        let dir = TempDir::new("manifest-torn").unwrap();
+        let manifest = &mut Manifest::create(dir.path(), 1, Version::default()).unwrap();
+        manifest
+            .log_and_apply(VersionEdit {
+                added: vec![(0, table(2))],
+                ..VersionEdit::default()
+            })
+            .unwrap();
+        manifest
+            .log_and_apply(VersionEdit {
+                added: vec![(0, table(3))],
+                ..VersionEdit::default()
+            })
+            .unwrap();
+
+        let path = manifest.path();
+        let length = fs::metadata(&path).unwrap().len();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(length - 3)
+            .unwrap();
+
+        // act
+        let recovered = Manifest::recover(dir.path()).unwrap().unwrap();
+
+        // assert
+        assert!(recovered.is_torn);
+        assert_eq!(recovered.version.levels, vec![vec![table(2)]]);
+    }
+
+    #[test]
+    fn recover_ignores_zeros_after_the_last_edit() {
+        // arrange
+        let dir = TempDir::new("manifest-zeroed").unwrap();
+        let manifest = &mut Manifest::create(dir.path(), 1, Version::default()).unwrap();
+        manifest
+            .log_and_apply(VersionEdit {
+                added: vec![(0, table(2))],
+                ..VersionEdit::default()
+            })
+            .unwrap();
+
+        let path = manifest.path();
+        let length = fs::metadata(&path).unwrap().len();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.extend_from_slice(&[0; 32]);
+        fs::write(&path, &bytes).unwrap();
+
+        // act
+        let recovered = Manifest::recover(dir.path()).unwrap().unwrap();
+
+        // assert
+        assert!(recovered.is_torn);
+        assert_eq!(recovered.version.levels, vec![vec![table(2)]]);
+        assert_eq!(fs::metadata(&path).unwrap().len(), length);
+    }
+
+    #[test]
+    fn recover_reports_a_corrupted_edit_before_the_end() {
+        // arrange
+        let dir = TempDir::new("manifest-corrupted").unwrap();
+        let manifest = &mut Manifest::create(dir.path(), 1, Version::default()).unwrap();
+        manifest
+            .log_and_apply(VersionEdit {
+                added: vec![(0, table(2))],
+                ..VersionEdit::default()
+            })
+            .unwrap();
+
+        let path = manifest.path();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[8] ^= 0xff; // payload of the snapshot, the first record
+        fs::write(&path, &bytes).unwrap();
+
+        // act
+        let recovered = Manifest::recover(dir.path());
+
+        // assert
+        match recovered {
+            Err(Error::Corruption { file, offset: 0 }) => assert_eq!(file, path),
+            _ => panic!("corrupted edit not reported"),
+        }
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn new_manifest_replaces_the_current_one() {
+        // arrange
+        let dir = TempDir::new("manifest-rotate").unwrap();
+        Manifest::create(dir.path(), 1, Version::default()).unwrap();
+
+        let mut version = Version::default();
+        version.apply(&VersionEdit {
+            added: vec![(0, table(2))],
+            ..VersionEdit::default()
+        });
+
+        // act
+        Manifest::create(dir.path(), 3, version.clone()).unwrap();
+
+        // assert
+        assert_eq!(
+            Manifest::recover(dir.path()).unwrap(),
+            Some(Recovered {
+                number: 3,
+                version,
+                is_torn: false
+            })
+        );
+        assert!(!dir.path().join("000003.tmp").exists());
+        assert_eq!(
+            Manifest::recover(TempDir::new("manifest-none").unwrap().path()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn recover_reports_a_missing_manifest() {
+        let dir = TempDir::new("manifest-missing").unwrap();
+        let manifest = Manifest::create(dir.path(), 1, Version::default()).unwrap();
+        fs::remove_file(manifest.path()).unwrap();
+
+        match Manifest::recover(dir.path()) {
+            Err(Error::NotFound(path)) => assert_eq!(path, manifest.path()),
+            _ => panic!("missing manifest not reported"),
+        }
+
+        fs::write(current_path(dir.path()), "000001.log\n").unwrap();
+
+        assert!(matches!(
+            Manifest::recover(dir.path()),
+            Err(Error::Corruption { offset: 0, .. })
+        ));
+    }
+}
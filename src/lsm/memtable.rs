@@ -0,0 +1,199 @@
+use crate::error::Result;
+use crate::lsm::batch::WriteBatch;
+use crate::lsm::codec::Codec;
+use crate::lsm::internal_key::InternalKey;
+use crate::lsm::record::Record;
+use crate::lsm::wal::{LogRecord, WriteAheadLog};
+use crate::symbol_table::red_black_bst::RedBlackBST;
+use crate::symbol_table::ST;
+use std::path::Path;
+
+// in-memory tree whose writes are appended to a write-ahead log before being applied.
+// Every write adds a version of its key, older versions are kept for the snapshots that
+// may still read them. Deletes are kept as tombstones so they can shadow keys already
+// flushed to tables.
+pub struct MemTable<KEY, VALUE>
+where
+    KEY: Ord,
+{
+    tree: RedBlackBST<InternalKey<KEY>, Record<VALUE>>,
+    log: WriteAheadLog,
+    size_in_bytes: usize, // encoded bytes written, every version included
+    last_sequence: u64,   // of the newest write held, 0 when empty
+}
+
+impl<KEY, VALUE> MemTable<KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone,
+    VALUE: Codec + Clone,
+{
+    // recovers whatever the log at path already holds and keeps appending to it
+    pub fn open(path: &Path) -> Result<MemTable<KEY, VALUE>> {
+        let mut tree = RedBlackBST::new();
+        let mut last_sequence = 0;
+
+        WriteAheadLog::replay(path, |sequence, record: LogRecord<KEY, VALUE>| {
+            let (key, record) = match record {
+                LogRecord::Put(key, value) => (key, Record::Value(value)),
+                LogRecord::Delete(key) => (key, Record::Tombstone),
+                LogRecord::Merge(key, operand) => (key, Record::Merge(operand)),
+            };
+
+            tree.put(InternalKey::new(key, sequence, record.kind()), record);
+            last_sequence = u64::max(last_sequence, sequence);
+        })?;
+
+        let log = WriteAheadLog::open(path)?;
+        let size_in_bytes = tree
+            .iter()
+            .map(|(key, record)| key.encoded_len() + record.encoded_len())
+            .sum();
+
+        Ok(MemTable {
+            tree,
+            log,
+            size_in_bytes,
+            last_sequence,
+        })
+    }
+
+    // sequence must be greater than that of every write already held
+    pub fn put(&mut self, sequence: u64, key: KEY, value: VALUE) -> Result<()> {
+        self.log.append_put(sequence, &key, &value)?;
+        self.insert(key, sequence, Record::Value(value));
+
+        Ok(())
+    }
+
+    pub fn delete(&mut self, sequence: u64, key: &KEY) -> Result<()> {
+        self.log.append_delete(sequence, key)?;
+        self.insert(key.clone(), sequence, Record::Tombstone);
+
+        Ok(())
+    }
+
+    pub fn merge(&mut self, sequence: u64, key: KEY, operand: VALUE) -> Result<()> {
+        self.log.append_merge(sequence, &key, &operand)?;
+        self.insert(key, sequence, Record::Merge(operand));
+
+        Ok(())
+    }
+
+    // logs the batch as a single record, its writes taking the sequences from sequence on
+    pub fn write(&mut self, sequence: u64, batch: WriteBatch<KEY, VALUE>) -> Result<()> {
+        self.log.append_batch(sequence, &batch)?;
+
+        for (sequence, operation) in (sequence..).zip(batch.into_operations()) {
+            let (key, record) = match operation {
+                LogRecord::Put(key, value) => (key, Record::Value(value)),
+                LogRecord::Delete(key) => (key, Record::Tombstone),
+                LogRecord::Merge(key, operand) => (key, Record::Merge(operand)),
+            };
+
+            self.insert(key, sequence, record);
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, user_key: KEY, sequence: u64, record: Record<VALUE>) {
+        let key = InternalKey::new(user_key, sequence, record.kind());
+        self.size_in_bytes += key.encoded_len() + record.encoded_len();
+        self.last_sequence = sequence;
+        self.tree.put(key, record);
+    }
+
+    // record of the newest version of the key written at or before sequence
+    #[cfg(test)]
+    pub fn get(&self, key: &KEY, sequence: u64) -> Option<&Record<VALUE>> {
+        self.version(key, sequence).map(|(_, record)| record)
+    }
+
+    pub fn version(&self, key: &KEY, sequence: u64) -> Option<(&InternalKey<KEY>, &Record<VALUE>)> {
+        let found = self
+            .tree
+            .ceiling(&InternalKey::lookup(key.clone(), sequence))?;
+
+        match found.user_key == *key {
+            true => self.tree.get(found).map(|record| (found, record)),
+            false => None,
+        }
+    }
+
+    pub fn tree(&self) -> &RedBlackBST<InternalKey<KEY>, Record<VALUE>> {
+        &self.tree
+    }
+
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.size_in_bytes
+    }
+
+    pub fn log_path(&self) -> &Path {
+        self.log.path()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsm::temp_dir::TempDir;
+
+    #[test]
+    fn reopened_memtable_has_previous_writes() {
+        // arrange
+        let dir = TempDir::new("memtable-reopen").unwrap();
+        let path = dir.path().join("000001.log");
+
+        {
+            let memtable = &mut MemTable::<String, u64>::open(&path).unwrap();
+            memtable.put(1, String::from("first"), 1).unwrap();
+            memtable.put(2, String::from("second"), 2).unwrap();
+            memtable.put(3, String::from("first"), 3).unwrap();
+            memtable.delete(4, &String::from("second")).unwrap();
+        }
+
+        // act
+        let memtable = MemTable::<String, u64>::open(&path).unwrap();
+
+        // assert
+        assert_eq!(memtable.tree().size(), 4);
+        assert_eq!(memtable.last_sequence(), 4);
+        assert_eq!(
+            memtable.get(&String::from("first"), 4),
+            Some(&Record::Value(3))
+        );
+        assert_eq!(
+            memtable.get(&String::from("second"), 4),
+            Some(&Record::Tombstone)
+        );
+        assert_eq!(memtable.log_path(), path.as_path());
+        assert_eq!(
+            memtable.size_in_bytes(),
+            2 * ("first".len() + 8 + 9) + ("second".len() + 8) * 2 + 9 + 1
+        );
+    }
+
+    #[test]
+    fn reads_at_a_sequence_ignore_newer_versions() {
+        let dir = TempDir::new("memtable-versions").unwrap();
+        let memtable = &mut MemTable::<String, u64>::open(&dir.path().join("000001.log")).unwrap();
+        memtable.put(1, String::from("a"), 1).unwrap();
+        memtable.put(2, String::from("b"), 2).unwrap();
+        memtable.put(3, String::from("a"), 3).unwrap();
+        memtable.delete(4, &String::from("b")).unwrap();
+
+        assert_eq!(memtable.get(&String::from("a"), 0), None);
+        assert_eq!(memtable.get(&String::from("a"), 2), Some(&Record::Value(1)));
+        assert_eq!(memtable.get(&String::from("a"), 3), Some(&Record::Value(3)));
+        assert_eq!(memtable.get(&String::from("b"), 3), Some(&Record::Value(2)));
+        assert_eq!(
+            memtable.get(&String::from("b"), 4),
+            Some(&Record::Tombstone)
+        );
+        assert_eq!(memtable.get(&String::from("c"), 4), None);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error::{Error, Result};
+use crate::priority_queue::MinPriorityQueue;
+use std::cmp::Ordering;
+
+// sorted entries with the sequence of the write that produced them, a greater
+// sequence meaning a newer version of the key
+pub type Source<'a, KEY, VALUE> = Box<dyn Iterator<Item = Result<(KEY, VALUE, u64)>> + 'a>;
+
+struct Head<KEY, VALUE> {
+    key: KEY,
+    value: VALUE,
+    sequence: u64,
+    source: usize,
+    reverse: bool,
+}
+
+// heads are popped in iteration order and, for equal keys, newest first
+impl<KEY: Ord, VALUE> Ord for Head<KEY, VALUE> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_key = if self.reverse {
+            other.key.cmp(&self.key)
+        } else {
+            self.key.cmp(&other.key)
+        };
+
+        by_key
+            .then_with(|| other.sequence.cmp(&self.sequence))
+            .then_with(|| self.source.cmp(&other.source))
+    }
+}
+
+impl<KEY: Ord, VALUE> PartialOrd for Head<KEY, VALUE> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<KEY: Ord, VALUE> PartialEq for Head<KEY, VALUE> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<KEY: Ord, VALUE> Eq for Head<KEY, VALUE> {}
+
+// K-way merge of sorted sources into a single sorted stream holding only the newest
+// version of every key. The heap keeps the current head of every source, so each
+// step costs O(log k) for k sources.
+pub struct MergingIterator<'a, KEY, VALUE>
+where
+    KEY: Ord,
+{
+    sources: Vec<Source<'a, KEY, VALUE>>,
+    heap: MinPriorityQueue<Head<KEY, VALUE>>,
+    reverse: bool,
+    error: Option<Error>,
+}
+
+impl<'a, KEY, VALUE> MergingIterator<'a, KEY, VALUE>
+where
+    KEY: Ord,
+{
+    // sources must be sorted ascending, or descending when reverse
+    pub fn new(
+        sources: Vec<Source<'a, KEY, VALUE>>,
+        reverse: bool,
+    ) -> MergingIterator<'a, KEY, VALUE> {
+        let mut merging = MergingIterator {
+            sources,
+            heap: MinPriorityQueue::new(),
+            reverse,
+            error: None,
+        };
+
+        for source in 0..merging.sources.len() {
+            if let Err(e) = merging.advance(source) {
+                merging.error = Some(e);
+                break;
+            }
+        }
+
+        merging
+    }
+
+    fn advance(&mut self, source: usize) -> Result<()> {
+        if let Some(entry) = self.sources[source].next() {
+            let (key, value, sequence) = entry?;
+
+            self.heap.push(Head {
+                key,
+                value,
+                sequence,
+                source,
+                reverse: self.reverse,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn fail(&mut self, e: Error) -> Option<Result<(KEY, VALUE, u64)>> {
+        self.heap = MinPriorityQueue::new();
+        self.sources.clear();
+        Some(Err(e))
+    }
+}
+
+impl<'a, KEY, VALUE> Iterator for MergingIterator<'a, KEY, VALUE>
+where
+    KEY: Ord,
+{
+    type Item = Result<(KEY, VALUE, u64)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            return self.fail(e);
+        }
+
+        let newest = self.heap.pop()?;
+
+        // a failing source still lets the entry already read through, the error is
+        // reported on the next call
+        if let Err(e) = self.advance(newest.source) {
+            self.error = Some(e);
+        }
+
+        // skip the older versions of the key other sources hold
+        while self.error.is_none() && self.heap.peek().is_some_and(|head| head.key == newest.key) {
+            let older = self.heap.pop().unwrap();
+
+            if let Err(e) = self.advance(older.source) {
+                self.error = Some(e);
+            }
+        }
+
+        Some(Ok((newest.key, newest.value, newest.sequence)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source<'a>(entries: Vec<(&'a str, u64, u64)>) -> Source<'a, &'a str, u64> {
+        Box::new(entries.into_iter().map(Ok))
+    }
+
+    #[test]
+    fn newest_version_of_each_key_wins() {
+        // arrange
+        let sources = vec![
+            source(vec![("A", 1, 1), ("C", 1, 1), ("E", 1, 1)]),
+            source(vec![("B", 2, 2), ("C", 2, 2), ("F", 2, 2)]),
+            source(vec![("A", 3, 3), ("F", 3, 3)]),
+            source(vec![]),
+        ];
+
+        // act
+        let merged: Vec<(&str, u64, u64)> = MergingIterator::new(sources, false)
+            .map(|entry| entry.unwrap())
+            .collect();
+
+        // assert
+        assert_eq!(
+            merged,
+            vec![
+                ("A", 3, 3),
+                ("B", 2, 2),
+                ("C", 2, 2),
+                ("E", 1, 1),
+                ("F", 3, 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn reverse_merge_of_descending_sources() {
+        let sources = vec![
+            source(vec![("E", 1, 1), ("C", 1, 1), ("A", 1, 1)]),
+            source(vec![("D", 2, 2), ("C", 2, 2)]),
+        ];
+
+        let keys: Vec<&str> = MergingIterator::new(sources, true)
+            .map(|entry| entry.unwrap().0)
+            .collect();
+
+        assert_eq!(keys, vec!["E", "D", "C", "A"]);
+    }
+
+    #[test]
+    fn source_errors_end_the_merge() {
+        let failing: Source<&str, u64> =
+            Box::new(vec![Ok(("A", 1, 1)), Err(Error::corruption("000001.sst", 0))].into_iter());
+        let sources = vec![failing, source(vec![("B", 2, 2), ("C", 2, 2)])];
+
+        let merged: Vec<Result<(&str, u64, u64)>> = MergingIterator::new(sources, false).collect();
+
+        assert_eq!(merged.len(), 2);
+        assert_eq!(merged[0].as_ref().unwrap().0, "A");
+        assert!(merged[1].is_err());
+    }
+}
@@ -0,0 +1,85 @@
+use crate::error::{Error, Result};
+use crate::lsm::record::Record;
+
+// Folds an operand written by LsmDb::merge into the value the key had, None when it had
+// none. Compactions fold consecutive operands before the value below them is known,
+// passing the older operand as the value, so the operation must be associative.
+pub trait MergeOperator<VALUE>: Send {
+    fn merge(&self, existing: Option<&VALUE>, operand: &VALUE) -> VALUE;
+}
+
+// adds operands, a missing value counting as 0
+pub struct U64Add;
+
+impl MergeOperator<u64> for U64Add {
+    fn merge(&self, existing: Option<&u64>, operand: &u64) -> u64 {
+        existing.map_or(*operand, |existing| existing.wrapping_add(*operand))
+    }
+}
+
+// record an operand amounts to once folded into the older record of its key, still an
+// operand when that one is too
+pub fn fold<VALUE>(
+    operator: &dyn MergeOperator<VALUE>,
+    operand: &VALUE,
+    older: Option<&Record<VALUE>>,
+) -> Record<VALUE> {
+    match older {
+        Some(Record::Merge(older)) => Record::Merge(operator.merge(Some(older), operand)),
+        Some(Record::Value(value)) => Record::Value(operator.merge(Some(value), operand)),
+        Some(Record::Tombstone) | None => Record::Value(operator.merge(None, operand)),
+    }
+}
+
+// record the versions of a key amount to, given newest first: the newest value or
+// deletion with the operands written after it folded in
+pub fn resolve<VALUE>(
+    operator: Option<&dyn MergeOperator<VALUE>>,
+    newest: Record<VALUE>,
+    mut older: impl Iterator<Item = Record<VALUE>>,
+) -> Result<Record<VALUE>> {
+    let mut record = newest;
+
+    while let Record::Merge(operand) = &record {
+        let operator = operator.ok_or_else(|| {
+            Error::InvalidArgument(String::from("merge operands read without a merge operator"))
+        })?;
+
+        record = fold(operator, operand, older.next().as_ref());
+    }
+
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operands_fold_down_to_the_newest_value_or_deletion() {
+        let resolved = |records: Vec<Record<u64>>| {
+            let mut records = records.into_iter();
+            let newest = records.next().unwrap();
+            resolve(Some(&U64Add), newest, records).unwrap()
+        };
+
+        assert_eq!(
+            resolved(vec![Record::Merge(1), Record::Merge(2), Record::Value(10)]),
+            Record::Value(13)
+        );
+        assert_eq!(
+            resolved(vec![Record::Merge(1), Record::Tombstone, Record::Value(10)]),
+            Record::Value(1)
+        );
+        assert_eq!(
+            resolved(vec![Record::Merge(1), Record::Merge(2)]),
+            Record::Value(3)
+        );
+        assert_eq!(
+            resolved(vec![Record::Value(5), Record::Merge(2)]),
+            Record::Value(5)
+        );
+        assert_eq!(fold(&U64Add, &1, Some(&Record::Merge(2))), Record::Merge(3));
+        assert!(resolve(None, Record::Merge(1), std::iter::empty()).is_err());
+    }
+}
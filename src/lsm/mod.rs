@@ -0,0 +1,19 @@
+pub mod append_only;
+pub mod batch;
+pub mod cache;
+pub mod codec;
+pub mod compaction;
+pub mod crc32c;
+pub mod db;
+pub mod filename;
+pub mod internal_key;
+pub mod manifest;
+pub mod memtable;
+pub mod merge;
+pub mod merge_operator;
+pub mod record;
+pub mod snapshot;
+pub mod sstable;
+pub mod temp_dir;
+pub mod transaction;
+pub mod wal;
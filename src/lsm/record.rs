@@ -0,0 +1,109 @@
+use crate::lsm::codec::Codec;
+use crate::lsm::internal_key::Kind;
+
+const VALUE: u8 = 1;
+const TOMBSTONE: u8 = 2;
+const MERGE: u8 = 3;
+
+// what the memtable and tables store for a key. A tombstone records a delete so it
+// shadows older values of the key living in other tables. A merge operand only has a
+// value once the merge operator folds it into the older records of the key.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Record<VALUE> {
+    Value(VALUE),
+    Tombstone,
+    Merge(VALUE),
+}
+
+impl<VALUE> Record<VALUE> {
+    // value of a record that is neither a tombstone nor an operand
+    pub fn value(&self) -> Option<&VALUE> {
+        match self {
+            Record::Value(value) => Some(value),
+            Record::Tombstone | Record::Merge(_) => None,
+        }
+    }
+
+    pub fn into_value(self) -> Option<VALUE> {
+        match self {
+            Record::Value(value) => Some(value),
+            Record::Tombstone | Record::Merge(_) => None,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn is_tombstone(&self) -> bool {
+        matches!(self, Record::Tombstone)
+    }
+
+    // kind of the version holding the record
+    pub fn kind(&self) -> Kind {
+        match self {
+            Record::Value(_) => Kind::Value,
+            Record::Tombstone => Kind::Deletion,
+            Record::Merge(_) => Kind::Merge,
+        }
+    }
+}
+
+impl<T: Codec> Codec for Record<T> {
+    fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Record::Value(value) => {
+                buffer.push(VALUE);
+                value.encode(buffer);
+            }
+            Record::Tombstone => buffer.push(TOMBSTONE),
+            Record::Merge(operand) => {
+                buffer.push(MERGE);
+                operand.encode(buffer);
+            }
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Record<T>> {
+        match *bytes.first()? {
+            VALUE => T::decode(&bytes[1..]).map(Record::Value),
+            TOMBSTONE if bytes.len() == 1 => Some(Record::Tombstone),
+            MERGE => T::decode(&bytes[1..]).map(Record::Merge),
+            _ => None,
+        }
+    }
+
+    fn encoded_len(&self) -> usize {
+        match self {
+            Record::Value(value) | Record::Merge(value) => 1 + value.encoded_len(),
+            Record::Tombstone => 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_values_and_tombstones() {
+        let value = Record::Value(String::from("word"));
+        let tombstone: Record<String> = Record::Tombstone;
+
+        let buffer = &mut Vec::new();
+        value.encode(buffer);
+        assert_eq!(buffer.len(), value.encoded_len());
+        assert_eq!(Record::decode(buffer), Some(value));
+
+        let buffer = &mut Vec::new();
+        tombstone.encode(buffer);
+        assert_eq!(buffer.len(), tombstone.encoded_len());
+        assert_eq!(Record::<String>::decode(buffer), Some(Record::Tombstone));
+
+        let operand = Record::Merge(7_u64);
+        let buffer = &mut Vec::new();
+        operand.encode(buffer);
+        assert_eq!(buffer.len(), operand.encoded_len());
+        assert_eq!(Record::decode(buffer), Some(operand));
+
+        assert_eq!(Record::<String>::decode(&[4]), None);
+        assert_eq!(Record::<String>::decode(&[]), None);
+    }
+}
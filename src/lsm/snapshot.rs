@@ -0,0 +1,248 @@
+use crate::lsm::internal_key::InternalKey;
+use crate::lsm::merge_operator::{self, MergeOperator};
+use crate::lsm::record::Record;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+// Sequences of the live snapshots of a database, shared with the snapshots themselves
+// so that dropping one releases it without reaching the database.
+#[derive(Clone, Default)]
+pub struct SnapshotList {
+    live: Arc<Mutex<BTreeMap<u64, usize>>>, // snapshots taken at every sequence
+}
+
+impl SnapshotList {
+    pub fn new() -> SnapshotList {
+        SnapshotList::default()
+    }
+
+    pub fn acquire(&self, sequence: u64) -> Snapshot {
+        *self.live.lock().unwrap().entry(sequence).or_insert(0) += 1;
+
+        Snapshot {
+            sequence,
+            list: self.clone(),
+        }
+    }
+
+    fn release(&self, sequence: u64) {
+        let mut live = self.live.lock().unwrap();
+
+        if let Some(count) = live.get_mut(&sequence) {
+            *count -= 1;
+
+            if *count == 0 {
+                live.remove(&sequence);
+            }
+        }
+    }
+
+    // sequences some snapshot is taken at, ascending
+    pub fn sequences(&self) -> Vec<u64> {
+        self.live.lock().unwrap().keys().copied().collect()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.live.lock().unwrap().is_empty()
+    }
+}
+
+// Point in time view of a database: reads made with it see the writes made up to its
+// sequence and none of the later ones. Compactions keep the versions it may read until
+// it is dropped.
+pub struct Snapshot {
+    sequence: u64,
+    list: SnapshotList,
+}
+
+impl Snapshot {
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        self.list.release(self.sequence);
+    }
+}
+
+// Which versions of a key flushes and compactions keep: the newest one every snapshot,
+// and reads made without one, may see. Merge operands are folded into the older versions
+// the same snapshots see, or kept along with them when there is no merge operator.
+pub struct Retention<'a, VALUE> {
+    snapshots: &'a [u64],  // ascending
+    drop_tombstones: bool, // nothing older lies below the output to shadow
+    operator: Option<&'a dyn MergeOperator<VALUE>>,
+}
+
+impl<'a, VALUE> Retention<'a, VALUE> {
+    pub fn new(
+        snapshots: &'a [u64],
+        drop_tombstones: bool,
+        operator: Option<&'a dyn MergeOperator<VALUE>>,
+    ) -> Retention<'a, VALUE> {
+        Retention {
+            snapshots,
+            drop_tombstones,
+            operator,
+        }
+    }
+
+    // a version is seen by the snapshots taken from its sequence on, up to the sequence
+    // of the next newer version. Versions seen by the same snapshots share a stripe.
+    fn stripe(&self, sequence: u64) -> usize {
+        self.snapshots
+            .partition_point(|&snapshot| snapshot < sequence)
+    }
+
+    // keeps the newest version of every stripe of the versions of a single user key,
+    // given newest first
+    pub fn retain<KEY>(&self, versions: &mut Vec<(InternalKey<KEY>, Record<VALUE>)>) {
+        let mut kept: Vec<(InternalKey<KEY>, Record<VALUE>)> = Vec::new();
+
+        for (key, record) in versions.drain(..) {
+            let stripe = self.stripe(key.sequence);
+            let newer = kept
+                .last_mut()
+                .filter(|(newer, _)| self.stripe(newer.sequence) == stripe);
+
+            match newer {
+                Some((newer, newer_record)) => {
+                    if let (Record::Merge(operand), Some(operator)) =
+                        (&*newer_record, self.operator)
+                    {
+                        let folded = merge_operator::fold(operator, operand, Some(&record));
+                        newer.kind = folded.kind();
+                        *newer_record = folded;
+                    } else if matches!(newer_record, Record::Merge(_)) {
+                        kept.push((key, record));
+                    }
+                }
+                None => kept.push((key, record)),
+            }
+        }
+
+        if self.drop_tombstones {
+            // the oldest operand has nothing older left to be folded into
+            if let (Some((key, record)), Some(operator)) = (kept.last_mut(), self.operator) {
+                if let Record::Merge(operand) = record {
+                    let folded = merge_operator::fold(operator, operand, None);
+                    key.kind = folded.kind();
+                    *record = folded;
+                }
+            }
+
+            // a tombstone left with no older version to hide reads just like a missing key
+            while kept.last().is_some_and(|(key, _)| key.is_deletion()) {
+                kept.pop();
+            }
+        }
+
+        *versions = kept;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsm::merge_operator::U64Add;
+
+    #[test]
+    fn dropped_snapshots_are_released() {
+        let list = SnapshotList::new();
+
+        let first = list.acquire(7);
+        let second = list.acquire(7);
+        let third = list.acquire(3);
+        assert_eq!(list.sequences(), vec![3, 7]);
+        assert_eq!(first.sequence(), 7);
+
+        drop(first);
+        assert_eq!(list.sequences(), vec![3, 7]);
+
+        drop(second);
+        drop(third);
+        assert!(list.is_empty());
+    }
+
+    fn versions(records: &[(u64, Option<u64>)]) -> Vec<(InternalKey<&str>, Record<u64>)> {
+        records
+            .iter()
+            .map(|&(sequence, value)| {
+                let record = value.map_or(Record::Tombstone, Record::Value);
+                (InternalKey::new("key", sequence, record.kind()), record)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn only_versions_some_read_sees_are_retained() {
+        let snapshots = [3, 7];
+        let retention = Retention::new(&snapshots, false, None);
+        let mut kept = versions(&[
+            (9, Some(9)),
+            (8, None),
+            (7, Some(7)),
+            (5, Some(5)),
+            (2, None),
+        ]);
+
+        retention.retain(&mut kept);
+
+        assert_eq!(kept, versions(&[(9, Some(9)), (7, Some(7)), (2, None)]));
+    }
+
+    #[test]
+    fn oldest_tombstones_are_dropped_when_nothing_lies_below() {
+        let snapshots = [3, 7];
+        let retention = Retention::new(&snapshots, true, None);
+        let mut kept = versions(&[(9, Some(9)), (6, None), (2, None)]);
+        let mut deleted = versions(&[(9, None)]);
+
+        retention.retain(&mut kept);
+        retention.retain(&mut deleted);
+
+        assert_eq!(kept, versions(&[(9, Some(9))]));
+        assert!(deleted.is_empty());
+    }
+
+    fn record(sequence: u64, record: Record<u64>) -> (InternalKey<&'static str>, Record<u64>) {
+        (InternalKey::new("key", sequence, record.kind()), record)
+    }
+
+    #[test]
+    fn operands_are_folded_within_their_stripe() {
+        let snapshots = [3, 7];
+        let merges = || {
+            vec![
+                record(9, Record::Merge(1)),
+                record(8, Record::Merge(2)),
+                record(6, Record::Merge(4)),
+                record(5, Record::Value(10)),
+                record(4, Record::Merge(8)),
+                record(2, Record::Merge(16)),
+            ]
+        };
+
+        let mut folded = merges();
+        Retention::new(&snapshots, false, Some(&U64Add)).retain(&mut folded);
+        let mut resolved = merges();
+        Retention::new(&snapshots, true, Some(&U64Add)).retain(&mut resolved);
+        let mut kept = merges();
+        Retention::new(&snapshots, false, None).retain(&mut kept);
+
+        assert_eq!(
+            folded,
+            vec![
+                record(9, Record::Merge(3)),
+                record(6, Record::Value(14)),
+                record(2, Record::Merge(16))
+            ]
+        );
+        assert_eq!(resolved[2], record(2, Record::Value(16)));
+        assert_eq!(kept.len(), 5);
+        assert_eq!(kept[4], record(2, Record::Merge(16)));
+    }
+}
@@ -0,0 +1,379 @@
+use crate::error::{Error, Result};
+use crate::lsm::codec::{self, Codec};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+// every RESTART_INTERVAL-th key is written whole, so that seeks only decode the keys
+// following the closest restart point
+pub const RESTART_INTERVAL: usize = 16;
+
+// Data block of key/value pairs in ascending key order. Keys only hold the bytes they do
+// not share with the previous key.
+//
+// layout: | entry 0 | ... | entry n | restart offsets (u32 each) | restart count (u32) |
+// entry: | shared (varint) | unshared (varint) | value length (varint) | key suffix | value |
+#[derive(Default)]
+pub struct BlockBuilder {
+    buffer: Vec<u8>,
+    restarts: Vec<u32>,
+    last_key: Vec<u8>,
+    key: Vec<u8>,   // reused to encode every key
+    value: Vec<u8>, // reused to encode every value
+    entries: usize,
+}
+
+impl BlockBuilder {
+    pub fn new() -> BlockBuilder {
+        BlockBuilder::default()
+    }
+
+    pub fn add<KEY: Codec, VALUE: Codec>(&mut self, key: &KEY, value: &VALUE) {
+        self.key.clear();
+        key.encode(&mut self.key);
+
+        let shared = if self.entries.is_multiple_of(RESTART_INTERVAL) {
+            self.restarts.push(self.buffer.len() as u32);
+            0
+        } else {
+            self.last_key
+                .iter()
+                .zip(&self.key)
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+
+        self.value.clear();
+        value.encode(&mut self.value);
+
+        codec::put_varint(&mut self.buffer, shared as u64);
+        codec::put_varint(&mut self.buffer, (self.key.len() - shared) as u64);
+        codec::put_varint(&mut self.buffer, self.value.len() as u64);
+        self.buffer.extend_from_slice(&self.key[shared..]);
+        self.buffer.extend_from_slice(&self.value);
+
+        std::mem::swap(&mut self.last_key, &mut self.key);
+        self.entries += 1;
+    }
+
+    // bytes of the finished block
+    pub fn size(&self) -> usize {
+        self.buffer.len() + 4 * self.restarts.len() + 4
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries == 0
+    }
+
+    // hands over the encoded block and leaves the builder empty
+    pub fn finish(&mut self) -> Vec<u8> {
+        for &restart in &self.restarts {
+            codec::put_u32(&mut self.buffer, restart);
+        }
+        codec::put_u32(&mut self.buffer, self.restarts.len() as u32);
+
+        self.restarts.clear();
+        self.last_key.clear();
+        self.entries = 0;
+        std::mem::take(&mut self.buffer)
+    }
+}
+
+// block read back from a table, decoded entry by entry as lookups walk it
+pub struct Block {
+    bytes: Vec<u8>,
+    restarts: usize, // offset of the restart array
+    restart_count: usize,
+    file: Arc<Path>, // where the block was read from, reported when it is corrupted
+    offset: u64,
+}
+
+impl Block {
+    pub fn new(bytes: Vec<u8>, file: Arc<Path>, offset: u64) -> Result<Block> {
+        let restart_count = bytes
+            .len()
+            .checked_sub(4)
+            .and_then(|offset| codec::get_u32(&bytes, offset));
+        let restarts = restart_count.and_then(|count| {
+            (count as usize)
+                .checked_mul(4)
+                .and_then(|size| bytes.len().checked_sub(size + 4))
+        });
+
+        match (restart_count, restarts) {
+            (Some(restart_count), Some(restarts)) => Ok(Block {
+                bytes,
+                restarts,
+                restart_count: restart_count as usize,
+                file,
+                offset,
+            }),
+            _ => Err(Error::corruption(&*file, offset)),
+        }
+    }
+
+    fn corrupted(&self) -> Error {
+        Error::corruption(&*self.file, self.offset)
+    }
+
+    fn decode<T: Codec>(&self, bytes: &[u8]) -> Result<T> {
+        T::decode(bytes).ok_or_else(|| self.corrupted())
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    fn restart_offset(&self, restart: usize) -> usize {
+        if restart < self.restart_count {
+            codec::get_u32(&self.bytes, self.restarts + 4 * restart).unwrap() as usize
+        } else {
+            self.restarts
+        }
+    }
+
+    // cursor on the entries from the restart point on
+    fn cursor(&self, restart: usize) -> BlockCursor<'_> {
+        BlockCursor {
+            block: self,
+            offset: self.restart_offset(restart),
+            key: Vec::new(),
+            value: 0..0,
+        }
+    }
+
+    // last restart point whose key is not greater than key, found by binary search
+    pub fn restart_for<KEY: Codec + Ord>(&self, key: &KEY) -> Result<usize> {
+        let (mut low, mut high) = (0, self.restart_count);
+
+        while low < high {
+            let middle = (low + high) / 2;
+            let mut cursor = self.cursor(middle);
+            cursor.advance()?;
+
+            if self.decode::<KEY>(cursor.key())? <= *key {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        Ok(low.saturating_sub(1))
+    }
+
+    // first entry at or after key that has the same user key
+    pub fn get<KEY, VALUE>(&self, key: &KEY) -> Result<Option<(KEY, VALUE)>>
+    where
+        KEY: Codec + Ord,
+        VALUE: Codec,
+    {
+        let mut encoded = Vec::new();
+        key.encode(&mut encoded);
+
+        let mut cursor = self.cursor(self.restart_for(key)?);
+
+        while cursor.advance()? {
+            let found = self.decode::<KEY>(cursor.key())?;
+
+            if found < *key {
+                continue;
+            }
+
+            if KEY::user_key(cursor.key()) == KEY::user_key(&encoded) {
+                return Ok(Some((found, self.decode(cursor.value())?)));
+            }
+
+            break;
+        }
+
+        Ok(None)
+    }
+
+    pub fn first_key<KEY: Codec>(&self) -> Result<Option<KEY>> {
+        let mut cursor = self.cursor(0);
+
+        match cursor.advance()? {
+            true => Ok(Some(self.decode(cursor.key())?)),
+            false => Ok(None),
+        }
+    }
+
+    // entries from the first restart point of the range up to the last one, excluded
+    pub fn entries<KEY, VALUE>(&self, restarts: Range<usize>) -> Result<Vec<(KEY, VALUE)>>
+    where
+        KEY: Codec,
+        VALUE: Codec,
+    {
+        let end = self.restart_offset(restarts.end);
+        let mut cursor = self.cursor(restarts.start);
+        let mut entries = Vec::new();
+
+        while cursor.offset < end && cursor.advance()? {
+            entries.push((self.decode(cursor.key())?, self.decode(cursor.value())?));
+        }
+
+        Ok(entries)
+    }
+
+    pub fn restart_count(&self) -> usize {
+        self.restart_count
+    }
+}
+
+struct BlockCursor<'a> {
+    block: &'a Block,
+    offset: usize, // of the next entry
+    key: Vec<u8>,
+    value: Range<usize>,
+}
+
+impl BlockCursor<'_> {
+    // moves to the next entry, false once past the last one
+    fn advance(&mut self) -> Result<bool> {
+        let bytes = &self.block.bytes[..self.block.restarts];
+
+        if self.offset >= bytes.len() {
+            return Ok(false);
+        }
+
+        let corrupted = || self.block.corrupted();
+
+        let (shared, offset) = codec::get_varint(bytes, self.offset).ok_or_else(corrupted)?;
+        let (unshared, offset) = codec::get_varint(bytes, offset).ok_or_else(corrupted)?;
+        let (value_length, offset) = codec::get_varint(bytes, offset).ok_or_else(corrupted)?;
+
+        let value_start = offset
+            .checked_add(unshared as usize)
+            .filter(|_| shared as usize <= self.key.len())
+            .ok_or_else(corrupted)?;
+        let value_end = value_start
+            .checked_add(value_length as usize)
+            .filter(|end| *end <= bytes.len())
+            .ok_or_else(corrupted)?;
+
+        self.key.truncate(shared as usize);
+        self.key.extend_from_slice(&bytes[offset..value_start]);
+        self.value = value_start..value_end;
+        self.offset = value_end;
+
+        Ok(true)
+    }
+
+    fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.block.bytes[self.value.clone()]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(bytes: Vec<u8>) -> Result<Block> {
+        Block::new(bytes, Arc::from(Path::new("000001.sst")), 4096)
+    }
+
+    fn words(count: usize) -> Block {
+        let builder = &mut BlockBuilder::new();
+
+        for i in 0..count {
+            builder.add(&format!("word{:04}", i * 2), &(i as u64));
+        }
+
+        read(builder.finish()).unwrap()
+    }
+
+    #[test]
+    fn entries_are_decoded_in_insertion_order() {
+        let builder = &mut BlockBuilder::new();
+        builder.add(&String::from("apple"), &1_u64);
+        builder.add(&String::from("applesauce"), &2_u64);
+        builder.add(&String::from("banana"), &3_u64);
+
+        let size = builder.size();
+        let block = read(builder.finish()).unwrap();
+        let entries = block.entries::<String, u64>(0..block.restart_count());
+
+        assert!(builder.is_empty());
+        assert_eq!(block.len(), size);
+        assert_eq!(
+            entries.unwrap(),
+            vec![
+                (String::from("apple"), 1),
+                (String::from("applesauce"), 2),
+                (String::from("banana"), 3)
+            ]
+        );
+    }
+
+    #[test]
+    fn shared_prefixes_are_written_once() {
+        let builder = &mut BlockBuilder::new();
+
+        for i in 0..RESTART_INTERVAL {
+            builder.add(
+                &format!("a-long-shared-prefix-{}", (b'a' + i as u8) as char),
+                &0_u64,
+            );
+        }
+
+        // one whole key, the others only keep their last character
+        let key_bytes = 22 + (RESTART_INTERVAL - 1);
+        assert_eq!(
+            builder.size(),
+            key_bytes + (3 + 8) * RESTART_INTERVAL + 4 + 4
+        );
+    }
+
+    #[test]
+    fn lookups_start_from_the_closest_restart_point() {
+        let block = words(100);
+
+        assert_eq!(block.restart_count(), 7);
+        assert_eq!(block.restart_for(&String::from("word0000")).unwrap(), 0);
+        assert_eq!(block.restart_for(&String::from("word0033")).unwrap(), 1);
+        assert_eq!(block.restart_for(&String::from("word0064")).unwrap(), 2);
+        assert_eq!(block.restart_for(&String::from("a")).unwrap(), 0);
+        assert_eq!(block.restart_for(&String::from("z")).unwrap(), 6);
+
+        for i in 0..100 {
+            let found = block.get::<String, u64>(&format!("word{:04}", i * 2));
+            let missing = block.get::<String, u64>(&format!("word{:04}", i * 2 + 1));
+
+            assert_eq!(
+                found.unwrap(),
+                Some((format!("word{:04}", i * 2), i as u64))
+            );
+            assert_eq!(missing.unwrap(), None);
+        }
+
+        let entries = block.entries::<String, u64>(2..4).unwrap();
+        assert_eq!(entries.len(), 2 * RESTART_INTERVAL);
+        assert_eq!(entries[0], (String::from("word0064"), 32));
+    }
+
+    #[test]
+    fn corrupted_blocks_are_rejected() {
+        let builder = &mut BlockBuilder::new();
+        builder.add(&String::from("apple"), &1_u64);
+        let bytes = builder.finish();
+
+        assert!(read(vec![1, 0, 0]).is_err());
+        assert!(read(vec![9, 0, 0, 0]).is_err());
+
+        let mut truncated = bytes[..bytes.len() - 10].to_vec();
+        truncated.extend_from_slice(&bytes[bytes.len() - 8..]);
+        let block = read(truncated).unwrap();
+
+        match block.entries::<String, u64>(0..1) {
+            Err(Error::Corruption { file, offset }) => {
+                assert_eq!(file, Path::new("000001.sst"));
+                assert_eq!(offset, 4096);
+            }
+            _ => panic!("corruption not detected"),
+        }
+    }
+}
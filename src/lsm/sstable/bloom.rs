@@ -0,0 +1,148 @@
+use crate::lsm::codec;
+
+// Bloom filter over the encoded keys of a table, answering "maybe present" or "surely
+// absent". Every key sets `probes` bits, derived from a single hash by double hashing.
+//
+// layout: | bit array | probes (u8) |
+pub struct BloomFilterBuilder {
+    bits_per_key: usize,
+    hashes: Vec<u32>,
+}
+
+impl BloomFilterBuilder {
+    pub fn new(bits_per_key: usize) -> BloomFilterBuilder {
+        BloomFilterBuilder {
+            bits_per_key,
+            hashes: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, key: &[u8]) {
+        self.hashes.push(hash(key));
+    }
+
+    // an empty filter, built without keys or bits, lets every key through
+    pub fn finish(self) -> Vec<u8> {
+        if self.bits_per_key == 0 || self.hashes.is_empty() {
+            return Vec::new();
+        }
+
+        // k = ln(2) * bits per key minimizes the false positive rate
+        let probes = ((self.bits_per_key as f64 * 0.69) as u8).clamp(1, 30);
+
+        // small filters have a high false positive rate, whatever the bits per key
+        let bits = usize::max(self.hashes.len() * self.bits_per_key, 64);
+        let bytes = bits.div_ceil(8);
+        let bits = bytes * 8;
+
+        let mut filter = vec![0; bytes + 1];
+
+        for &hash in &self.hashes {
+            for bit in probe_bits(hash, probes, bits) {
+                filter[bit / 8] |= 1 << (bit % 8);
+            }
+        }
+
+        filter[bytes] = probes;
+        filter
+    }
+}
+
+pub struct BloomFilter {
+    bytes: Vec<u8>,
+}
+
+impl BloomFilter {
+    pub fn new(bytes: Vec<u8>) -> BloomFilter {
+        BloomFilter { bytes }
+    }
+
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        let (probes, bits) = match self.bytes.split_last() {
+            Some((&probes, bits)) if !bits.is_empty() => (probes, bits),
+            _ => return true,
+        };
+
+        probe_bits(hash(key), probes, bits.len() * 8)
+            .all(|bit| bits[bit / 8] & (1 << (bit % 8)) != 0)
+    }
+}
+
+fn probe_bits(hash: u32, probes: u8, bits: usize) -> impl Iterator<Item = usize> {
+    let delta = hash.rotate_right(17);
+
+    (0..probes as u32)
+        .map(move |probe| hash.wrapping_add(delta.wrapping_mul(probe)) as usize % bits)
+}
+
+// Murmur-like hash of the key bytes
+fn hash(bytes: &[u8]) -> u32 {
+    const SEED: u32 = 0xbc9f_1d34;
+    const M: u32 = 0xc6a4_a793;
+
+    let mut h = SEED ^ (bytes.len() as u32).wrapping_mul(M);
+    let mut chunks = bytes.chunks_exact(4);
+
+    for chunk in chunks.by_ref() {
+        h = h.wrapping_add(codec::get_u32(chunk, 0).unwrap());
+        h = h.wrapping_mul(M);
+        h ^= h >> 16;
+    }
+
+    let rest = chunks.remainder();
+
+    if !rest.is_empty() {
+        for (position, &byte) in rest.iter().enumerate() {
+            h = h.wrapping_add((byte as u32) << (8 * position));
+        }
+
+        h = h.wrapping_mul(M);
+        h ^= h >> 24;
+    }
+
+    h
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(i: u32) -> Vec<u8> {
+        format!("key{}", i).into_bytes()
+    }
+
+    fn build(bits_per_key: usize, keys: impl Iterator<Item = u32>) -> BloomFilter {
+        let mut builder = BloomFilterBuilder::new(bits_per_key);
+
+        for i in keys {
+            builder.add(&key(i));
+        }
+
+        BloomFilter::new(builder.finish())
+    }
+
+    #[test]
+    fn added_keys_are_always_found() {
+        let filter = build(10, 0..10_000);
+
+        assert!((0..10_000).all(|i| filter.may_contain(&key(i))));
+    }
+
+    #[test]
+    fn absent_keys_are_mostly_ruled_out() {
+        let filter = build(10, 0..10_000);
+
+        let false_positives = (10_000..20_000)
+            .filter(|&i| filter.may_contain(&key(i)))
+            .count();
+
+        // about 1% for 10 bits per key
+        assert!(false_positives < 200, "{} false positives", false_positives);
+    }
+
+    #[test]
+    fn empty_filter_lets_every_key_through() {
+        assert!(build(10, 0..0).may_contain(&key(1)));
+        assert!(build(0, 0..100).may_contain(&key(1000)));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::lsm::codec;
+
+// Codec of a data block, whose id is written after the block so that a table may mix
+// blocks written with different settings.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Lz, // LZ77 byte-oriented compression in the spirit of Snappy
+}
+
+// compressed blocks are only kept when they save at least an eighth of the bytes
+const MIN_SAVING_RATIO: usize = 8;
+
+impl Compression {
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Lz => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Compression> {
+        match id {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz),
+            _ => None,
+        }
+    }
+
+    // appends the block followed by the id of the codec it was written with, which is
+    // none when compressing would not pay off
+    pub fn write_block(self, block: &[u8], buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+
+        if self == Compression::Lz {
+            lz::compress(block, buffer);
+
+            if buffer.len() - start < block.len() - block.len() / MIN_SAVING_RATIO {
+                buffer.push(Compression::Lz.id());
+                return;
+            }
+
+            buffer.truncate(start);
+        }
+
+        buffer.extend_from_slice(block);
+        buffer.push(Compression::None.id());
+    }
+
+    // contents of a block read from disk, codec id included, none when it cannot be
+    // decompressed
+    pub fn read_block(bytes: &[u8]) -> Option<Vec<u8>> {
+        let (&id, block) = bytes.split_last()?;
+
+        match Compression::from_id(id)? {
+            Compression::None => Some(block.to_vec()),
+            Compression::Lz => lz::decompress(block),
+        }
+    }
+}
+
+// Stream of literal runs and copies of earlier bytes, after the uncompressed length:
+//
+// | length (u32) | token | bytes or offset | token | ... |
+//
+// a token below 128 announces token + 1 literal bytes, any other token a copy of
+// token - 128 + MIN_MATCH bytes starting offset (u16) bytes back.
+mod lz {
+    use super::*;
+
+    const MIN_MATCH: usize = 4;
+    const MAX_MATCH: usize = 127 + MIN_MATCH;
+    const MAX_LITERALS: usize = 128;
+    const MAX_OFFSET: usize = u16::MAX as usize;
+    const HASH_BITS: u32 = 14;
+
+    pub fn compress(input: &[u8], buffer: &mut Vec<u8>) {
+        codec::put_u32(buffer, input.len() as u32);
+
+        // last position of every hashed 4-byte sequence, offset by one so that zero is empty
+        let mut table = vec![0_usize; 1 << HASH_BITS];
+        let mut literals = 0;
+        let mut position = 0;
+
+        while position + MIN_MATCH <= input.len() {
+            let slot = hash(&input[position..position + MIN_MATCH]);
+            let candidate = table[slot].checked_sub(1);
+            table[slot] = position + 1;
+
+            let candidate = match candidate {
+                Some(candidate)
+                    if position - candidate <= MAX_OFFSET
+                        && input[candidate..candidate + MIN_MATCH]
+                            == input[position..position + MIN_MATCH] =>
+                {
+                    candidate
+                }
+                _ => {
+                    position += 1;
+                    continue;
+                }
+            };
+
+            let length = (MIN_MATCH..MAX_MATCH)
+                .take_while(|&length| {
+                    position + length < input.len()
+                        && input[candidate + length] == input[position + length]
+                })
+                .last()
+                .map_or(MIN_MATCH, |length| length + 1);
+
+            put_literals(&input[literals..position], buffer);
+
+            buffer.push((128 + length - MIN_MATCH) as u8);
+            buffer.extend_from_slice(&((position - candidate) as u16).to_le_bytes());
+
+            position += length;
+            literals = position;
+        }
+
+        put_literals(&input[literals..], buffer);
+    }
+
+    fn put_literals(literals: &[u8], buffer: &mut Vec<u8>) {
+        for run in literals.chunks(MAX_LITERALS) {
+            buffer.push((run.len() - 1) as u8);
+            buffer.extend_from_slice(run);
+        }
+    }
+
+    pub fn decompress(bytes: &[u8]) -> Option<Vec<u8>> {
+        let length = codec::get_u32(bytes, 0)? as usize;
+        let mut output = Vec::with_capacity(length);
+        let mut offset = 4;
+
+        while offset < bytes.len() {
+            let token = bytes[offset] as usize;
+            offset += 1;
+
+            if token < 128 {
+                let run = bytes.get(offset..offset + token + 1)?;
+                output.extend_from_slice(run);
+                offset += run.len();
+            } else {
+                let distance = bytes.get(offset..offset + 2)?;
+                let distance = u16::from_le_bytes([distance[0], distance[1]]) as usize;
+                offset += 2;
+
+                if distance == 0 || distance > output.len() {
+                    return None;
+                }
+
+                // copies overlap their source when the distance is shorter than the copy
+                let start = output.len() - distance;
+                for i in 0..token - 128 + MIN_MATCH {
+                    output.push(output[start + i]);
+                }
+            }
+
+            if output.len() > length {
+                return None;
+            }
+        }
+
+        Some(output).filter(|output| output.len() == length)
+    }
+
+    fn hash(bytes: &[u8]) -> usize {
+        let word = codec::get_u32(bytes, 0).unwrap();
+        (word.wrapping_mul(0x1e35_a7bd) >> (32 - HASH_BITS)) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(compression: Compression, block: &[u8]) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        compression.write_block(block, &mut buffer);
+
+        assert_eq!(Compression::read_block(&buffer).unwrap(), block);
+        buffer
+    }
+
+    #[test]
+    fn repetitive_blocks_shrink() {
+        let block: Vec<u8> = (0..2000)
+            .flat_map(|i| format!("key{:05}value{}", i, i % 7).into_bytes())
+            .collect();
+
+        let written = roundtrip(Compression::Lz, &block);
+
+        assert_eq!(written.last(), Some(&Compression::Lz.id()));
+        assert!(written.len() < block.len() / 2, "{} bytes", written.len());
+    }
+
+    #[test]
+    fn overlapping_copies_repeat_short_sequences() {
+        let block = [vec![b'x'], vec![b'a'; 300], b"abcabcabcabcabc".to_vec()].concat();
+
+        let written = roundtrip(Compression::Lz, &block);
+
+        assert!(written.len() < 30, "{} bytes", written.len());
+    }
+
+    #[test]
+    fn incompressible_blocks_are_stored_as_they_are() {
+        let mut state = 0x2545_f491_u32;
+        let block: Vec<u8> = (0..1000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+
+        let written = roundtrip(Compression::Lz, &block);
+
+        assert_eq!(written.last(), Some(&Compression::None.id()));
+        assert_eq!(written.len(), block.len() + 1);
+        assert_eq!(roundtrip(Compression::Lz, b"").len(), 1);
+        assert_eq!(roundtrip(Compression::None, b"abcdabcdabcd").len(), 13);
+    }
+
+    #[test]
+    fn corrupted_blocks_are_rejected() {
+        let mut buffer = Vec::new();
+        Compression::Lz.write_block(&[b'a'; 100], &mut buffer);
+
+        assert_eq!(Compression::read_block(&buffer[..buffer.len() - 2]), None);
+        assert_eq!(Compression::read_block(&[1, 2, 3, 9]), None);
+        assert_eq!(Compression::read_block(&[]), None);
+    }
+}
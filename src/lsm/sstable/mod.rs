@@ -0,0 +1,155 @@
+// Sorted string table layout:
+//
+// | data block 0 | ... | data block n | filter block | index block | footer |
+//
+// data blocks hold key/value pairs in ascending key order, keys sharing their prefix with
+// the previous key (see block.rs), the filter block holds a Bloom filter of every key, the
+// index block holds the last key and the location of every data block and the fixed size
+// footer locates the filter and index blocks. Every block is followed by a trailer:
+//
+// | compression codec id (u8) | crc32c of the block and codec id (u32) |
+//
+// and the footer is laid out as:
+//
+// | index handle | filter handle | entry count (u64) | version (u32) | crc32c (u32) | magic (u64) |
+//
+// its checksum covering the fields before it.
+pub mod block;
+pub mod bloom;
+pub mod compression;
+pub mod reader;
+pub mod writer;
+
+use crate::lsm::codec;
+use crate::lsm::crc32c;
+use crate::lsm::sstable::compression::Compression;
+
+pub const MAGIC: u64 = 0x6c73_6d64_6273_7374; // "lsmdbsst"
+pub const VERSION: u32 = 6;
+pub const BLOCK_TRAILER_SIZE: usize = 5;
+pub const FOOTER_SIZE: usize = 56;
+pub const BLOCK_SIZE: usize = 4096;
+
+pub struct TableOptions {
+    pub bits_per_key: usize, // Bloom filter bits for every key, none disables the filter
+    pub compression: Compression, // of the data blocks
+}
+
+impl Default for TableOptions {
+    fn default() -> TableOptions {
+        TableOptions {
+            bits_per_key: 10,
+            compression: Compression::Lz,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockHandle {
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl BlockHandle {
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        codec::put_u64(buffer, self.offset);
+        codec::put_u64(buffer, self.size);
+    }
+
+    pub fn decode(bytes: &[u8], offset: usize) -> Option<(BlockHandle, usize)> {
+        let handle = BlockHandle {
+            offset: codec::get_u64(bytes, offset)?,
+            size: codec::get_u64(bytes, offset + 8)?,
+        };
+
+        Some((handle, offset + 16))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Footer {
+    pub index: BlockHandle,
+    pub filter: BlockHandle,
+    pub entry_count: u64,
+}
+
+impl Footer {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(FOOTER_SIZE);
+        self.index.encode(&mut buffer);
+        self.filter.encode(&mut buffer);
+        codec::put_u64(&mut buffer, self.entry_count);
+        codec::put_u32(&mut buffer, VERSION);
+        let checksum = crc32c::checksum(&buffer);
+        codec::put_u32(&mut buffer, checksum);
+        codec::put_u64(&mut buffer, MAGIC);
+        buffer
+    }
+
+    // none for a file that is not a table of this version or whose footer is corrupted
+    pub fn decode(bytes: &[u8]) -> Option<Footer> {
+        if bytes.len() != FOOTER_SIZE
+            || codec::get_u64(bytes, 48) != Some(MAGIC)
+            || codec::get_u32(bytes, 40) != Some(VERSION)
+            || codec::get_u32(bytes, 44) != Some(crc32c::checksum(&bytes[..44]))
+        {
+            return None;
+        }
+
+        let (index, offset) = BlockHandle::decode(bytes, 0)?;
+        let (filter, offset) = BlockHandle::decode(bytes, offset)?;
+        let entry_count = codec::get_u64(bytes, offset)?;
+
+        Some(Footer {
+            index,
+            filter,
+            entry_count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn footer_roundtrip() {
+        let footer = Footer {
+            index: BlockHandle {
+                offset: 8192,
+                size: 120,
+            },
+            filter: BlockHandle {
+                offset: 8000,
+                size: 192,
+            },
+            entry_count: 300,
+        };
+
+        let bytes = footer.encode();
+
+        assert_eq!(bytes.len(), FOOTER_SIZE);
+        assert_eq!(Footer::decode(&bytes), Some(footer));
+    }
+
+    #[test]
+    fn footer_rejects_wrong_magic_version_and_checksum() {
+        let footer = Footer {
+            index: BlockHandle { offset: 0, size: 0 },
+            filter: BlockHandle { offset: 0, size: 0 },
+            entry_count: 0,
+        };
+
+        let mut bytes = footer.encode();
+        bytes[40] = 99;
+        assert!(Footer::decode(&bytes).is_none());
+
+        let mut bytes = footer.encode();
+        bytes[50] ^= 0xff;
+        assert!(Footer::decode(&bytes).is_none());
+
+        let mut bytes = footer.encode();
+        bytes[3] ^= 0xff;
+        assert!(Footer::decode(&bytes).is_none());
+    }
+}
@@ -0,0 +1,768 @@
+use crate::error::{Error, Result};
+use crate::lsm::cache::{BlockCache, BlockKind};
+use crate::lsm::codec::{self, Codec};
+use crate::lsm::crc32c;
+use crate::lsm::sstable::block::Block;
+use crate::lsm::sstable::bloom::BloomFilter;
+use crate::lsm::sstable::compression::Compression;
+use crate::lsm::sstable::{BlockHandle, Footer, FOOTER_SIZE};
+use crate::symbol_table::{is_after_start, is_before_end, ReadOnlyST};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::{AddAssign, Bound, Range, RangeBounds};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+// outcome of the Bloom filter checks made by point lookups
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FilterStats {
+    pub hits: u64,            // the filter let the key through and the table held it
+    pub misses: u64,          // the filter ruled the key out, no block was read
+    pub false_positives: u64, // the filter let through a key the table did not hold
+}
+
+impl AddAssign for FilterStats {
+    fn add_assign(&mut self, other: FilterStats) {
+        self.hits += other.hits;
+        self.misses += other.misses;
+        self.false_positives += other.false_positives;
+    }
+}
+
+// last key of every data block
+type Index<KEY> = Vec<(KEY, BlockHandle)>;
+
+// read-only view of a table file. Index, filter and data blocks are read through the
+// block cache shared by the tables of a database, only the footer and the key range are
+// kept by the table itself.
+pub struct Table<KEY, VALUE> {
+    file: Mutex<File>,
+    path: Arc<Path>,
+    cache: Arc<BlockCache>,
+    file_id: u64,
+    footer: Footer,
+    key_range: Option<(KEY, KEY)>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    false_positives: AtomicU64,
+    file_size: u64,
+    value: PhantomData<VALUE>,
+}
+
+impl<KEY, VALUE> Table<KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    pub fn open(path: &Path, cache: &Arc<BlockCache>) -> Result<Table<KEY, VALUE>> {
+        let mut file = File::open(path)?;
+        let file_size = file.metadata()?.len();
+
+        if file_size < FOOTER_SIZE as u64 {
+            return Err(Error::corruption(path, 0));
+        }
+
+        let offset = file_size - FOOTER_SIZE as u64;
+        let footer = BlockHandle {
+            offset,
+            size: FOOTER_SIZE as u64,
+        };
+        let footer = Footer::decode(&read_at(&mut file, footer)?)
+            .ok_or_else(|| Error::corruption(path, offset))?;
+
+        let mut table: Table<KEY, VALUE> = Table {
+            file: Mutex::new(file),
+            path: Arc::from(path),
+            cache: cache.clone(),
+            file_id: cache.new_file_id(),
+            footer,
+            key_range: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            false_positives: AtomicU64::new(0),
+            file_size,
+            value: PhantomData,
+        };
+
+        let index = table.index()?;
+
+        if let (Some((_, first_handle)), Some((last_key, _))) = (index.first(), index.last()) {
+            let first_key = table.read_block(*first_handle)?.first_key()?;
+            table.key_range = first_key.map(|first_key| (first_key, last_key.clone()));
+        }
+
+        Ok(table)
+    }
+
+    // contents of the block, once its checksum is verified and it is decompressed
+    fn read(&self, handle: BlockHandle) -> Result<Vec<u8>> {
+        let corrupted = || Error::corruption(&*self.path, handle.offset);
+
+        // a handle read from a corrupted block must not make us allocate or read past
+        // the blocks of the file
+        let end = handle
+            .offset
+            .checked_add(handle.size)
+            .ok_or_else(corrupted)?;
+
+        if end > self.file_size - FOOTER_SIZE as u64 {
+            return Err(corrupted());
+        }
+
+        let bytes =
+            read_at(&mut self.file.lock().unwrap(), handle).map_err(|e| match e.kind() {
+                std::io::ErrorKind::UnexpectedEof => corrupted(),
+                _ => Error::Io(e),
+            })?;
+
+        let (block, checksum) = bytes.split_at(bytes.len().checked_sub(4).ok_or_else(corrupted)?);
+
+        if codec::get_u32(checksum, 0) != Some(crc32c::checksum(block)) {
+            return Err(corrupted());
+        }
+
+        Compression::read_block(block).ok_or_else(corrupted)
+    }
+
+    fn index(&self) -> Result<Arc<Index<KEY>>> {
+        let handle = self.footer.index;
+
+        self.cache
+            .get_or_load((self.file_id, handle.offset), BlockKind::Index, || {
+                let bytes = self.read(handle)?;
+                let mut index = Vec::new();
+                let mut offset = 0;
+
+                while offset < bytes.len() {
+                    let entry = codec::get_length_prefixed::<KEY>(&bytes, offset).and_then(
+                        |(key, next)| {
+                            let (block, next) = BlockHandle::decode(&bytes, next)?;
+                            Some((key, block, next))
+                        },
+                    );
+                    let (key, block, next) =
+                        entry.ok_or_else(|| Error::corruption(&*self.path, handle.offset))?;
+
+                    index.push((key, block));
+                    offset = next;
+                }
+
+                Ok((index, bytes.len()))
+            })
+    }
+
+    fn filter(&self) -> Result<Arc<BloomFilter>> {
+        let handle = self.footer.filter;
+
+        self.cache
+            .get_or_load((self.file_id, handle.offset), BlockKind::Filter, || {
+                let bytes = self.read(handle)?;
+                let size = bytes.len();
+                Ok((BloomFilter::new(bytes), size))
+            })
+    }
+
+    fn read_block(&self, handle: BlockHandle) -> Result<Arc<Block>> {
+        self.cache
+            .get_or_load((self.file_id, handle.offset), BlockKind::Data, || {
+                let block = Block::new(self.read(handle)?, self.path.clone(), handle.offset)?;
+                let size = block.len();
+                Ok((block, size))
+            })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // bytes on disk
+    pub fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    // smallest and greatest keys, none for an empty table
+    pub fn key_range(&self) -> Option<(&KEY, &KEY)> {
+        self.key_range.as_ref().map(|(first, last)| (first, last))
+    }
+
+    pub fn filter_stats(&self) -> FilterStats {
+        FilterStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            false_positives: self.false_positives.load(Ordering::Relaxed),
+        }
+    }
+
+    // value of the first entry at or after key that has the same user key, see
+    // Codec::user_key, along with its key. Consults the filter before reading any data
+    // block.
+    pub fn get_entry(&self, key: &KEY) -> Result<Option<(KEY, VALUE)>> {
+        let mut encoded = Vec::new();
+        key.encode(&mut encoded);
+
+        if !self.filter()?.may_contain(KEY::user_key(&encoded)) {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        }
+
+        let entry = self.search(key)?;
+
+        let counter = match entry {
+            Some(_) => &self.hits,
+            None => &self.false_positives,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        Ok(entry)
+    }
+
+    fn search(&self, key: &KEY) -> Result<Option<(KEY, VALUE)>> {
+        let index = self.index()?;
+        let handle = match index.get(block_for(&index, key)) {
+            Some((_, handle)) => *handle,
+            None => return Ok(None),
+        };
+
+        self.read_block(handle)?.get(key)
+    }
+
+    pub fn range<R: RangeBounds<KEY>>(&self, range: R) -> TableIter<'_, KEY, VALUE> {
+        TableIter::new(self, &range, false)
+    }
+
+    // iterates the range in descending key order
+    pub fn range_rev<R: RangeBounds<KEY>>(&self, range: R) -> TableIter<'_, KEY, VALUE> {
+        TableIter::new(self, &range, true)
+    }
+}
+
+impl<KEY, VALUE> ReadOnlyST<KEY, VALUE> for Table<KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    fn size(&self) -> usize {
+        self.footer.entry_count as usize
+    }
+
+    // value of the entry get_entry finds
+    fn get(&self, key: &KEY) -> Result<Option<VALUE>> {
+        Ok(self.get_entry(key)?.map(|(_, value)| value))
+    }
+
+    fn min(&self) -> Option<&KEY> {
+        self.key_range().map(|(first, _)| first)
+    }
+
+    fn max(&self) -> Option<&KEY> {
+        self.key_range().map(|(_, last)| last)
+    }
+
+    fn floor(&self, key: &KEY) -> Result<Option<KEY>> {
+        let entry = self
+            .range_rev((Bound::Unbounded, Bound::Included(key)))
+            .next();
+        Ok(entry.transpose()?.map(|(key, _)| key))
+    }
+
+    fn ceiling(&self, key: &KEY) -> Result<Option<KEY>> {
+        let entry = self.range((Bound::Included(key), Bound::Unbounded)).next();
+        Ok(entry.transpose()?.map(|(key, _)| key))
+    }
+
+    fn keys_in_range(&self, min_key: &KEY, max_key: &KEY) -> Result<Vec<KEY>> {
+        self.range((Bound::Included(min_key), Bound::Included(max_key)))
+            .map(|entry| entry.map(|(key, _)| key))
+            .collect()
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = Result<(KEY, VALUE)>>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        self.range(..)
+    }
+}
+
+fn read_at(file: &mut File, handle: BlockHandle) -> std::io::Result<Vec<u8>> {
+    let mut buffer = vec![0; handle.size as usize];
+    file.seek(SeekFrom::Start(handle.offset))?;
+    file.read_exact(&mut buffer)?;
+
+    Ok(buffer)
+}
+
+// position of the first block whose keys may be greater or equal to key
+fn block_for<KEY: Ord>(index: &Index<KEY>, key: &KEY) -> usize {
+    index.partition_point(|(last_key, _)| last_key < key)
+}
+
+// walks a key range of the table, loading one data block at a time
+pub struct TableIter<'a, KEY, VALUE> {
+    table: &'a Table<KEY, VALUE>,
+    index: Option<Arc<Index<KEY>>>, // loaded by the first call to next
+    next_block: Option<usize>,
+    entries: Vec<(KEY, VALUE)>, // entries of the current block, next one at the end
+    range: (Bound<KEY>, Bound<KEY>),
+    reverse: bool,
+}
+
+impl<'a, KEY, VALUE> TableIter<'a, KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    fn new<R: RangeBounds<KEY>>(
+        table: &'a Table<KEY, VALUE>,
+        range: &R,
+        reverse: bool,
+    ) -> TableIter<'a, KEY, VALUE> {
+        TableIter {
+            table,
+            index: None,
+            next_block: None,
+            entries: Vec::new(),
+            range: (range.start_bound().cloned(), range.end_bound().cloned()),
+            reverse,
+        }
+    }
+
+    // block holding the first key of the range in the order of iteration
+    fn first_block(&self, index: &Index<KEY>) -> Option<usize> {
+        if self.reverse {
+            let last_block = match &self.range.1 {
+                Bound::Included(end) | Bound::Excluded(end) => block_for(index, end),
+                Bound::Unbounded => index.len(),
+            };
+
+            index
+                .len()
+                .checked_sub(1)
+                .map(|block| usize::min(last_block, block))
+        } else {
+            let first_block = match &self.range.0 {
+                Bound::Included(start) | Bound::Excluded(start) => block_for(index, start),
+                Bound::Unbounded => 0,
+            };
+
+            Some(first_block).filter(|block| *block < index.len())
+        }
+    }
+
+    // restart points of the first block that may hold keys of the range, so that seeks
+    // skip the entries before them
+    fn first_restarts(&self, block: &Block) -> Result<Range<usize>> {
+        let restarts = 0..block.restart_count();
+
+        match (&self.range, self.reverse) {
+            ((Bound::Included(start) | Bound::Excluded(start), _), false) => {
+                Ok(block.restart_for(start)?..restarts.end)
+            }
+            ((_, Bound::Included(end) | Bound::Excluded(end)), true) => {
+                Ok(0..block.restart_for(end)? + 1)
+            }
+            _ => Ok(restarts),
+        }
+    }
+
+    fn load_next_block(&mut self) -> Result<bool> {
+        let (index, first) = match &self.index {
+            Some(index) => (index.clone(), false),
+            None => {
+                let index = self.table.index()?;
+                self.next_block = self.first_block(&index);
+                self.index = Some(index.clone());
+                (index, true)
+            }
+        };
+
+        let block = match self.next_block {
+            Some(block) => block,
+            None => return Ok(false),
+        };
+
+        self.next_block = if self.reverse {
+            block.checked_sub(1)
+        } else {
+            Some(block + 1).filter(|next| *next < index.len())
+        };
+
+        let block = self.table.read_block(index[block].1)?;
+        let restarts = match first {
+            true => self.first_restarts(&block)?,
+            false => 0..block.restart_count(),
+        };
+        self.entries = block.entries(restarts)?;
+
+        if !self.reverse {
+            self.entries.reverse();
+        }
+
+        Ok(true)
+    }
+
+    fn finish(&mut self) {
+        self.entries.clear();
+        self.next_block = None;
+        // an index that failed to load is not loaded again
+        self.index.get_or_insert_with(|| Arc::new(Vec::new()));
+    }
+}
+
+impl<'a, KEY, VALUE> Iterator for TableIter<'a, KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    type Item = Result<(KEY, VALUE)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.pop() {
+                let (not_reached, passed) = if self.reverse {
+                    (
+                        !is_before_end(&self.range, &entry.0),
+                        !is_after_start(&self.range, &entry.0),
+                    )
+                } else {
+                    (
+                        !is_after_start(&self.range, &entry.0),
+                        !is_before_end(&self.range, &entry.0),
+                    )
+                };
+
+                if not_reached {
+                    continue;
+                }
+
+                if passed {
+                    self.finish();
+                    return None;
+                }
+
+                return Some(Ok(entry));
+            }
+
+            match self.load_next_block() {
+                Ok(true) => continue,
+                Ok(false) => return None,
+                Err(e) => {
+                    self.finish();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsm::sstable::writer::write_table;
+    use crate::lsm::sstable::TableOptions;
+    use crate::lsm::temp_dir::TempDir;
+    use crate::symbol_table::red_black_bst::RedBlackBST;
+    use crate::symbol_table::ST;
+
+    fn bytes(text: &str) -> Vec<u8> {
+        text.as_bytes().to_vec()
+    }
+
+    fn write_words(dir: &TempDir) -> Table<Vec<u8>, Vec<u8>> {
+        let st = &mut RedBlackBST::<Vec<u8>, Vec<u8>>::new();
+
+        for (position, key) in "S E A R C H E X A M P L E".split(" ").enumerate() {
+            st.put(bytes(key), bytes(&format!("{}", position)));
+        }
+
+        let path = dir.path().join("000001.sst");
+        write_table(&path, st, &TableOptions::default()).unwrap();
+
+        Table::open(&path, &BlockCache::new(1 << 20)).unwrap()
+    }
+
+    // enough entries to spread the table over many data blocks
+    fn write_numbers(dir: &TempDir) -> Table<Vec<u8>, Vec<u8>> {
+        let st = &mut RedBlackBST::<Vec<u8>, Vec<u8>>::new();
+
+        for i in (0..5000).step_by(2) {
+            st.put(
+                bytes(&format!("key{:05}", i)),
+                bytes(&format!("value{}", i)),
+            );
+        }
+
+        let path = dir.path().join("000002.sst");
+        write_table(&path, st, &TableOptions::default()).unwrap();
+
+        Table::open(&path, &BlockCache::new(1 << 20)).unwrap()
+    }
+
+    #[test]
+    fn get_finds_values_written_from_memtable() {
+        let dir = TempDir::new("sstable-get").unwrap();
+        let table = write_words(&dir);
+
+        assert_eq!(table.size(), 10);
+        assert_eq!(table.get(&bytes("A")).unwrap(), Some(bytes("8")));
+        assert_eq!(table.get(&bytes("E")).unwrap(), Some(bytes("12")));
+        assert_eq!(table.get(&bytes("X")).unwrap(), Some(bytes("7")));
+        assert_eq!(table.get(&bytes("T")).unwrap(), None);
+        assert!(table.contains(&bytes("S")).unwrap());
+        assert!(!table.contains(&bytes("Z")).unwrap());
+    }
+
+    #[test]
+    fn filter_counts_lookups_it_lets_through_and_rules_out() {
+        let dir = TempDir::new("sstable-filter").unwrap();
+        let table = write_numbers(&dir);
+
+        for i in 0..5000 {
+            let expected = (i % 2 == 0).then(|| bytes(&format!("value{}", i)));
+            assert_eq!(
+                table.get(&bytes(&format!("key{:05}", i))).unwrap(),
+                expected
+            );
+        }
+
+        let stats = table.filter_stats();
+
+        assert_eq!(stats.hits, 2500);
+        assert_eq!(stats.misses + stats.false_positives, 2500);
+        assert!(stats.false_positives < 100, "{:?}", stats);
+    }
+
+    #[test]
+    fn blocks_are_read_once_through_the_cache() {
+        let dir = TempDir::new("sstable-cache").unwrap();
+        let table = write_numbers(&dir);
+        let opened = table.cache.stats();
+
+        for _ in 0..3 {
+            assert_eq!(
+                table.get(&bytes("key02000")).unwrap(),
+                Some(bytes("value2000"))
+            );
+        }
+
+        let stats = table.cache.stats();
+
+        // opening the table read the index and the first data block
+        assert_eq!(opened.index.misses, 1);
+        assert_eq!(stats.index.misses, 1);
+        assert_eq!(stats.filter.misses, 1);
+        assert_eq!(stats.filter.hits, 2);
+        assert_eq!(stats.data.misses, opened.data.misses + 1);
+        assert_eq!(stats.data.hits, opened.data.hits + 2);
+        assert!(stats.usage as u64 <= table.file_size());
+    }
+
+    #[test]
+    fn tables_read_the_same_whatever_their_compression() {
+        let dir = TempDir::new("sstable-compression").unwrap();
+        let compressed = write_numbers(&dir);
+
+        let path = dir.path().join("000003.sst");
+        let options = TableOptions {
+            compression: Compression::None,
+            ..TableOptions::default()
+        };
+        let st: RedBlackBST<Vec<u8>, Vec<u8>> = compressed.iter().map(|entry| entry.unwrap()).fold(
+            RedBlackBST::new(),
+            |mut st, (key, value)| {
+                st.put(key, value);
+                st
+            },
+        );
+        write_table(&path, &st, &options).unwrap();
+        let uncompressed = Table::open(&path, &BlockCache::new(1 << 20)).unwrap();
+
+        assert!(compressed.file_size() < uncompressed.file_size());
+        assert!(compressed
+            .iter()
+            .map(Result::unwrap)
+            .eq(uncompressed.iter().map(Result::unwrap)));
+        assert_eq!(
+            uncompressed.get(&bytes("key01234")).unwrap(),
+            Some(bytes("value1234"))
+        );
+    }
+
+    #[test]
+    fn ordered_operations_match_memtable() {
+        let dir = TempDir::new("sstable-ordered").unwrap();
+        let table = write_words(&dir);
+
+        assert_eq!(table.min(), Some(&bytes("A")));
+        assert_eq!(table.max(), Some(&bytes("X")));
+        assert_eq!(table.floor(&bytes("J")).unwrap(), Some(bytes("H")));
+        assert_eq!(table.floor(&bytes("M")).unwrap(), Some(bytes("M")));
+        assert_eq!(table.floor(&bytes("0")).unwrap(), None);
+        assert_eq!(table.ceiling(&bytes("D")).unwrap(), Some(bytes("E")));
+        assert_eq!(table.ceiling(&bytes("Z")).unwrap(), None);
+        assert_eq!(
+            table.keys_in_range(&bytes("D"), &bytes("R")).unwrap(),
+            vec![
+                bytes("E"),
+                bytes("H"),
+                bytes("L"),
+                bytes("M"),
+                bytes("P"),
+                bytes("R")
+            ]
+        );
+    }
+
+    #[test]
+    fn lookups_cross_block_boundaries() {
+        let dir = TempDir::new("sstable-blocks").unwrap();
+        let table = write_numbers(&dir);
+
+        let index = table.index().unwrap();
+
+        assert!(index.len() > 1);
+        assert_eq!(table.size(), 2500);
+        assert_eq!(
+            table.get(&bytes("key04000")).unwrap(),
+            Some(bytes("value4000"))
+        );
+        assert_eq!(table.get(&bytes("key04001")).unwrap(), None);
+
+        for (last_key, _) in index.iter() {
+            let next = table
+                .ceiling(&[last_key.clone(), vec![0]].concat())
+                .unwrap();
+            let floor = table.floor(&[last_key.clone(), vec![0]].concat()).unwrap();
+
+            assert_eq!(floor.as_ref(), Some(last_key));
+            assert!(next.is_none() || next.as_ref() > Some(last_key));
+        }
+
+        let keys = table
+            .keys_in_range(&bytes("key01001"), &bytes("key03000"))
+            .unwrap();
+        assert_eq!(keys.len(), 1000);
+        assert_eq!(keys.first(), Some(&bytes("key01002")));
+        assert_eq!(keys.last(), Some(&bytes("key03000")));
+        assert_eq!(table.iter().count(), 2500);
+    }
+
+    #[test]
+    fn ranges_iterate_in_both_directions_across_blocks() {
+        let dir = TempDir::new("sstable-ranges").unwrap();
+        let table = write_numbers(&dir);
+
+        let forward: Vec<Vec<u8>> = table
+            .range(bytes("key01001")..bytes("key03000"))
+            .map(|entry| entry.unwrap().0)
+            .collect();
+        let backward: Vec<Vec<u8>> = table
+            .range_rev(bytes("key01001")..bytes("key03000"))
+            .map(|entry| entry.unwrap().0)
+            .collect();
+
+        assert_eq!(forward.len(), 999);
+        assert_eq!(forward.first(), Some(&bytes("key01002")));
+        assert_eq!(forward.last(), Some(&bytes("key02998")));
+        assert!(backward.iter().rev().eq(forward.iter()));
+
+        assert_eq!(table.range_rev(..).count(), 2500);
+        assert_eq!(
+            table.range_rev(..).next().unwrap().unwrap().0,
+            bytes("key04998")
+        );
+        assert_eq!(table.range(bytes("key9")..).count(), 0);
+        assert_eq!(table.range_rev(..bytes("key00000")).count(), 0);
+    }
+
+    #[test]
+    fn empty_memtable_writes_empty_table() {
+        let dir = TempDir::new("sstable-empty").unwrap();
+        let path = dir.path().join("000001.sst");
+        write_table(
+            &path,
+            &RedBlackBST::<Vec<u8>, Vec<u8>>::new(),
+            &TableOptions::default(),
+        )
+        .unwrap();
+
+        let table = Table::<Vec<u8>, Vec<u8>>::open(&path, &BlockCache::new(1 << 20)).unwrap();
+
+        assert!(table.is_empty());
+        assert_eq!(table.min(), None);
+        assert_eq!(table.max(), None);
+        assert_eq!(table.floor(&bytes("A")).unwrap(), None);
+        assert_eq!(table.get(&bytes("A")).unwrap(), None);
+        assert_eq!(table.range_rev(..).count(), 0);
+    }
+
+    #[test]
+    fn open_rejects_files_that_are_not_tables() {
+        let dir = TempDir::new("sstable-invalid").unwrap();
+        let path = dir.path().join("000001.sst");
+        std::fs::write(&path, vec![7; 64]).unwrap();
+
+        assert!(Table::<Vec<u8>, Vec<u8>>::open(&path, &BlockCache::new(1 << 20)).is_err());
+    }
+
+    #[test]
+    fn corrupted_blocks_fail_their_checksum() {
+        // arrange
+        let dir = TempDir::new("sstable-checksum").unwrap();
+        let path = write_numbers(&dir).path().to_path_buf();
+        let index = Table::<Vec<u8>, Vec<u8>>::open(&path, &BlockCache::new(1 << 20))
+            .unwrap()
+            .index()
+            .unwrap();
+        let (last_key, handle) = index.last().unwrap().clone();
+
+        let mut file = std::fs::read(&path).unwrap();
+        file[handle.offset as usize + 10] ^= 0x01;
+        std::fs::write(&path, &file).unwrap();
+
+        // act
+        let table = Table::<Vec<u8>, Vec<u8>>::open(&path, &BlockCache::new(1 << 20)).unwrap();
+
+        // assert
+        match table.get(&last_key) {
+            Err(Error::Corruption { file, offset }) => {
+                assert_eq!(file, path);
+                assert_eq!(offset, handle.offset);
+            }
+            other => panic!("corruption not detected: {:?}", other),
+        }
+        assert!(table.get(&bytes("key00000")).unwrap().is_some());
+        assert!(table.iter().any(|entry| entry.is_err()));
+    }
+
+    #[test]
+    fn handles_past_the_blocks_are_corruption() {
+        // arrange
+        let dir = TempDir::new("sstable-handles").unwrap();
+        let path = write_numbers(&dir).path().to_path_buf();
+
+        let mut file = std::fs::read(&path).unwrap();
+        let footer_offset = file.len() - FOOTER_SIZE;
+        let mut footer = Footer::decode(&file[footer_offset..]).unwrap();
+        let index = footer.index;
+        footer.index.size = u64::MAX / 2;
+        file.truncate(footer_offset);
+        file.extend(footer.encode());
+        std::fs::write(&path, &file).unwrap();
+
+        // act
+        let opened = Table::<Vec<u8>, Vec<u8>>::open(&path, &BlockCache::new(1 << 20));
+
+        // assert
+        match opened {
+            Err(Error::Corruption { file, offset }) => {
+                assert_eq!(file, path);
+                assert_eq!(offset, index.offset);
+            }
+            _ => panic!("index handle past the blocks accepted"),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use crate::error::Result;
+use crate::lsm::codec::{self, Codec};
+use crate::lsm::crc32c;
+use crate::lsm::sstable::block::BlockBuilder;
+use crate::lsm::sstable::bloom::BloomFilterBuilder;
+use crate::lsm::sstable::compression::Compression;
+use crate::lsm::sstable::{BlockHandle, Footer, TableOptions, BLOCK_SIZE, BLOCK_TRAILER_SIZE};
+use crate::symbol_table::ST;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+// writes entries, which must be added in ascending key order, as a sorted string table
+pub struct TableWriter {
+    file: BufWriter<File>,
+    offset: u64,
+    block: BlockBuilder,
+    last_key: Vec<u8>,
+    index: Vec<u8>,
+    filter: BloomFilterBuilder,
+    compression: Compression,
+    entry_count: u64,
+}
+
+impl TableWriter {
+    pub fn create(path: &Path, options: &TableOptions) -> Result<TableWriter> {
+        Ok(TableWriter {
+            file: BufWriter::new(File::create(path)?),
+            offset: 0,
+            block: BlockBuilder::new(),
+            last_key: Vec::new(),
+            index: Vec::new(),
+            filter: BloomFilterBuilder::new(options.bits_per_key),
+            compression: options.compression,
+            entry_count: 0,
+        })
+    }
+
+    pub fn add<KEY: Codec, VALUE: Codec>(&mut self, key: &KEY, value: &VALUE) -> Result<()> {
+        self.block.add(key, value);
+        self.entry_count += 1;
+
+        self.last_key.clear();
+        key.encode(&mut self.last_key);
+        self.filter.add(KEY::user_key(&self.last_key));
+
+        if self.block.size() >= BLOCK_SIZE {
+            self.flush_block()?;
+        }
+
+        Ok(())
+    }
+
+    // bytes written so far, the pending block included
+    pub fn file_size(&self) -> u64 {
+        self.offset + self.block.size() as u64
+    }
+
+    fn flush_block(&mut self) -> Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+
+        let block = self.block.finish();
+        let handle = self.write_block(&block, self.compression)?;
+
+        codec::put_length_prefixed(&mut self.index, &self.last_key);
+        handle.encode(&mut self.index);
+
+        Ok(())
+    }
+
+    // writes the block compressed, followed by its trailer
+    fn write_block(&mut self, block: &[u8], compression: Compression) -> Result<BlockHandle> {
+        let mut bytes = Vec::with_capacity(block.len() + BLOCK_TRAILER_SIZE);
+        compression.write_block(block, &mut bytes);
+        let checksum = crc32c::checksum(&bytes);
+        codec::put_u32(&mut bytes, checksum);
+
+        self.write_raw(&bytes)
+    }
+
+    fn write_raw(&mut self, bytes: &[u8]) -> Result<BlockHandle> {
+        self.file.write_all(bytes)?;
+
+        let handle = BlockHandle {
+            offset: self.offset,
+            size: bytes.len() as u64,
+        };
+        self.offset += bytes.len() as u64;
+
+        Ok(handle)
+    }
+
+    // writes pending data, the filter and index blocks and the footer, returning the
+    // table size
+    pub fn finish(mut self) -> Result<u64> {
+        self.flush_block()?;
+
+        let filter = std::mem::replace(&mut self.filter, BloomFilterBuilder::new(0)).finish();
+        // filter bits do not compress
+        let filter = self.write_block(&filter, Compression::None)?;
+
+        let index = std::mem::take(&mut self.index);
+        let footer = Footer {
+            index: self.write_block(&index, self.compression)?,
+            filter,
+            entry_count: self.entry_count,
+        };
+        self.write_raw(&footer.encode())?;
+
+        self.file.flush()?;
+        self.file.get_ref().sync_all()?;
+
+        Ok(self.offset)
+    }
+}
+
+// writes a symbol table to a new table file
+pub fn write_table<KEY, VALUE>(
+    path: &Path,
+    st: &impl ST<KEY, VALUE>,
+    options: &TableOptions,
+) -> Result<u64>
+where
+    KEY: Codec + Ord,
+    VALUE: Codec,
+{
+    let mut writer = TableWriter::create(path, options)?;
+
+    for (key, value) in st.iter() {
+        writer.add(key, value)?;
+    }
+
+    writer.finish()
+}
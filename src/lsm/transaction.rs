@@ -0,0 +1,187 @@
+use crate::error::Result;
+use crate::lsm::batch::WriteBatch;
+use crate::lsm::codec::Codec;
+use crate::lsm::db::LsmDb;
+use crate::lsm::record::Record;
+use crate::lsm::snapshot::Snapshot;
+use std::collections::{BTreeMap, BTreeSet};
+
+// Optimistic transaction: reads see the database as of the snapshot it began with plus
+// its own writes, which are buffered until commit. Nothing is locked meanwhile, so
+// LsmDb::commit instead checks that no key it read was written since it began and fails
+// with Error::Conflict otherwise, leaving the caller to retry it from a new one.
+pub struct Transaction<KEY, VALUE> {
+    snapshot: Snapshot,
+    reads: BTreeSet<KEY>,
+    writes: BTreeMap<KEY, Record<VALUE>>, // last write of every key
+}
+
+impl<KEY, VALUE> Transaction<KEY, VALUE>
+where
+    KEY: Codec + Ord + Clone + Send + Sync + 'static,
+    VALUE: Codec + Clone + Send + Sync + 'static,
+{
+    pub fn new(snapshot: Snapshot) -> Transaction<KEY, VALUE> {
+        Transaction {
+            snapshot,
+            reads: BTreeSet::new(),
+            writes: BTreeMap::new(),
+        }
+    }
+
+    // sequence of the snapshot the transaction reads
+    pub fn sequence(&self) -> u64 {
+        self.snapshot.sequence()
+    }
+
+    pub fn get(&mut self, db: &LsmDb<KEY, VALUE>, key: &KEY) -> Result<Option<VALUE>> {
+        if let Some(record) = self.writes.get(key) {
+            return Ok(record.value().cloned());
+        }
+
+        self.reads.insert(key.clone());
+        db.get_at(key, &self.snapshot)
+    }
+
+    pub fn put(&mut self, key: KEY, value: VALUE) {
+        self.writes.insert(key, Record::Value(value));
+    }
+
+    pub fn delete(&mut self, key: KEY) {
+        self.writes.insert(key, Record::Tombstone);
+    }
+
+    // keys whose value some read returned, the missing ones included
+    pub fn reads(&self) -> impl Iterator<Item = &KEY> {
+        self.reads.iter()
+    }
+
+    pub fn into_batch(self) -> WriteBatch<KEY, VALUE> {
+        let mut batch = WriteBatch::new();
+
+        for (key, record) in self.writes {
+            match record {
+                Record::Value(value) => batch.put(key, value),
+                Record::Tombstone => batch.delete(key),
+                Record::Merge(operand) => batch.merge(key, operand),
+            }
+        }
+
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::lsm::db::Options;
+    use crate::symbol_table::ST;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    fn key(key: &str) -> String {
+        String::from(key)
+    }
+
+    #[test]
+    fn reads_see_the_snapshot_and_own_writes() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(Options::default()).unwrap();
+        db.put(key("a"), 1);
+        db.put(key("b"), 2);
+        let transaction = &mut db.begin();
+
+        // act
+        db.put(key("a"), 10);
+        transaction.put(key("c"), 3);
+        transaction.delete(key("b"));
+
+        // assert
+        assert_eq!(transaction.get(db, &key("a")).unwrap(), Some(1));
+        assert_eq!(transaction.get(db, &key("b")).unwrap(), None);
+        assert_eq!(transaction.get(db, &key("c")).unwrap(), Some(3));
+        assert_eq!(transaction.get(db, &key("d")).unwrap(), None);
+        assert!(transaction.reads().eq([&key("a"), &key("d")]));
+        assert_eq!(db.get(&key("c")), None);
+    }
+
+    #[test]
+    fn commits_fail_when_a_read_key_was_written_since() {
+        // arrange
+        let db = &mut LsmDb::<String, u64>::temporary(Options::default()).unwrap();
+        db.put(key("from"), 10);
+
+        let mut moving = db.begin();
+        let from = moving.get(db, &key("from")).unwrap().unwrap();
+        moving.put(key("from"), from - 3);
+        moving.put(key("to"), 3);
+
+        let mut blind = db.begin();
+        blind.put(key("to"), 1);
+
+        let mut missing = db.begin();
+        missing.get(db, &key("new")).unwrap();
+        missing.put(key("from"), 0);
+
+        // act
+        db.put(key("from"), 20);
+        db.put(key("new"), 1);
+        db.flush().unwrap();
+
+        // assert
+        assert!(matches!(db.commit(moving), Err(Error::Conflict)));
+        assert!(matches!(db.commit(missing), Err(Error::Conflict)));
+        assert_eq!(db.get(&key("from")), Some(&20));
+        assert_eq!(db.get(&key("to")), None);
+
+        db.commit(blind).unwrap();
+        assert_eq!(db.get(&key("to")), Some(&1));
+    }
+
+    #[test]
+    fn concurrent_increments_are_not_lost() {
+        // arrange
+        let db = Arc::new(Mutex::new(
+            LsmDb::<String, u64>::temporary(Options::default()).unwrap(),
+        ));
+        let words = ["it", "was", "the", "best", "of", "times"];
+
+        // act
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let db = Arc::clone(&db);
+
+                thread::spawn(move || {
+                    for word in words.iter().cycle().take(300) {
+                        loop {
+                            let mut transaction = db.lock().unwrap().begin();
+                            let count = transaction
+                                .get(&db.lock().unwrap(), &key(word))
+                                .unwrap()
+                                .unwrap_or(0);
+                            transaction.put(key(word), count + 1);
+
+                            match db.lock().unwrap().commit(transaction) {
+                                Ok(()) => break,
+                                Err(Error::Conflict) => continue,
+                                Err(e) => panic!("{}", e),
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        // assert
+        let db = db.lock().unwrap();
+
+        for word in words {
+            assert_eq!(db.get(&key(word)), Some(&200));
+        }
+    }
+}
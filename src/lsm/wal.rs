@@ -0,0 +1,491 @@
+use crate::error::{Error, Result};
+use crate::lsm::batch::WriteBatch;
+use crate::lsm::codec::{self, Codec};
+use crate::lsm::crc32c;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+// every record is laid out as: checksum (u32) | payload length (u32) | payload
+//
+// and the payload of a write as: kind (u8) | sequence (u64) | key and value
+//
+// A batch is a single record whose writes take consecutive sequences from its own:
+// BATCH (u8) | sequence (u64) | count (u32) | kind (u8) | key | value | kind (u8) | ...
+// with keys and values length prefixed.
+const HEADER_SIZE: usize = 8;
+
+const PUT: u8 = 1;
+const DELETE: u8 = 2;
+const BATCH: u8 = 3;
+const MERGE: u8 = 4;
+
+pub enum LogRecord<KEY, VALUE> {
+    Put(KEY, VALUE),
+    Delete(KEY),
+    Merge(KEY, VALUE), // operand for the merge operator
+}
+
+pub struct WriteAheadLog {
+    file: File,
+    path: PathBuf,
+}
+
+impl WriteAheadLog {
+    // opens the log for appending, creating it when it does not exist
+    pub fn open(path: &Path) -> Result<WriteAheadLog> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(WriteAheadLog {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn append_put<KEY: Codec, VALUE: Codec>(
+        &mut self,
+        sequence: u64,
+        key: &KEY,
+        value: &VALUE,
+    ) -> Result<()> {
+        let mut payload = vec![PUT];
+        codec::put_u64(&mut payload, sequence);
+        codec::put_length_prefixed(&mut payload, key);
+        value.encode(&mut payload);
+
+        self.append(&payload)
+    }
+
+    pub fn append_merge<KEY: Codec, VALUE: Codec>(
+        &mut self,
+        sequence: u64,
+        key: &KEY,
+        operand: &VALUE,
+    ) -> Result<()> {
+        let mut payload = vec![MERGE];
+        codec::put_u64(&mut payload, sequence);
+        codec::put_length_prefixed(&mut payload, key);
+        operand.encode(&mut payload);
+
+        self.append(&payload)
+    }
+
+    pub fn append_delete<KEY: Codec>(&mut self, sequence: u64, key: &KEY) -> Result<()> {
+        let mut payload = vec![DELETE];
+        codec::put_u64(&mut payload, sequence);
+        key.encode(&mut payload);
+
+        self.append(&payload)
+    }
+
+    // the writes of the batch get sequence, sequence + 1, ... in the order they were added
+    pub fn append_batch<KEY: Codec, VALUE: Codec>(
+        &mut self,
+        sequence: u64,
+        batch: &WriteBatch<KEY, VALUE>,
+    ) -> Result<()> {
+        let mut payload = vec![BATCH];
+        codec::put_u64(&mut payload, sequence);
+        codec::put_u32(&mut payload, batch.len() as u32);
+
+        for operation in batch.operations() {
+            match operation {
+                LogRecord::Put(key, value) => {
+                    payload.push(PUT);
+                    codec::put_length_prefixed(&mut payload, key);
+                    codec::put_length_prefixed(&mut payload, value);
+                }
+                LogRecord::Delete(key) => {
+                    payload.push(DELETE);
+                    codec::put_length_prefixed(&mut payload, key);
+                }
+                LogRecord::Merge(key, operand) => {
+                    payload.push(MERGE);
+                    codec::put_length_prefixed(&mut payload, key);
+                    codec::put_length_prefixed(&mut payload, operand);
+                }
+            }
+        }
+
+        self.append(&payload)
+    }
+
+    // appends a checksummed record holding any payload but an empty one
+    pub fn append(&mut self, payload: &[u8]) -> Result<()> {
+        let mut record = Vec::with_capacity(HEADER_SIZE + payload.len());
+        codec::put_u32(&mut record, crc32c::checksum(payload));
+        codec::put_u32(&mut record, payload.len() as u32);
+        record.extend_from_slice(payload);
+
+        // a single write so a crash can only tear the last record
+        Ok(self.file.write_all(&record)?)
+    }
+
+    pub fn sync(&self) -> Result<()> {
+        Ok(self.file.sync_data()?)
+    }
+
+    // feeds every write to apply along with its sequence, in the order they were written,
+    // the writes of a batch one by one. Replays the log like replay_records.
+    pub fn replay<KEY, VALUE, F>(path: &Path, mut apply: F) -> Result<()>
+    where
+        KEY: Codec,
+        VALUE: Codec,
+        F: FnMut(u64, LogRecord<KEY, VALUE>),
+    {
+        WriteAheadLog::replay_records(path, WriteAheadLog::decode_writes, |writes| {
+            for (sequence, record) in writes {
+                apply(sequence, record);
+            }
+        })?;
+
+        Ok(())
+    }
+
+    // feeds every record decode accepts to apply, in the order they were written. A bad
+    // record, one cut short, failing its checksum or not decoding, with no intact record
+    // after it marks where a crash interrupted the log: whatever the crash left there,
+    // zeros included, is truncated and later appends continue from the last intact record.
+    // A bad record followed by intact ones was damaged after it was written and fails the
+    // replay with Error::Corruption instead, keeping the file as it is. Returns whether
+    // there was a torn tail.
+    pub fn replay_records<T, D, F>(path: &Path, decode: D, mut apply: F) -> Result<bool>
+    where
+        D: Fn(&[u8]) -> Option<T>,
+        F: FnMut(T),
+    {
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut offset = 0;
+
+        while let Some((record, end)) = WriteAheadLog::read_record(&bytes, offset, &decode) {
+            apply(record);
+            offset = end;
+        }
+
+        let is_torn = offset < bytes.len();
+
+        if is_torn {
+            // the length of a bad record can't be trusted, so intact records are looked
+            // for at every offset after it
+            let is_followed = (offset + 1..bytes.len())
+                .any(|start| WriteAheadLog::read_record(&bytes, start, &decode).is_some());
+
+            if is_followed {
+                return Err(Error::corruption(path, offset as u64));
+            }
+
+            let file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok(is_torn)
+    }
+
+    // the decoded record at offset and the offset it ends at, none when the record is cut
+    // short by the end of the file, fails its checksum or does not decode. Nothing appends
+    // an empty payload, which is what zeros left by a crash would otherwise read as.
+    fn read_record<T>(
+        bytes: &[u8],
+        offset: usize,
+        decode: &impl Fn(&[u8]) -> Option<T>,
+    ) -> Option<(T, usize)> {
+        let checksum = codec::get_u32(bytes, offset)?;
+        let length = codec::get_u32(bytes, offset + 4)? as usize;
+
+        let start = offset + HEADER_SIZE;
+        let payload = bytes.get(start..start.checked_add(length)?)?;
+
+        if payload.is_empty() || crc32c::checksum(payload) != checksum {
+            return None;
+        }
+
+        Some((decode(payload)?, start + length))
+    }
+
+    // the writes a record holds, a batch only decoding when all of its writes do
+    fn decode_writes<KEY: Codec, VALUE: Codec>(
+        payload: &[u8],
+    ) -> Option<Vec<(u64, LogRecord<KEY, VALUE>)>> {
+        let sequence = codec::get_u64(payload, 1)?;
+
+        let record = match payload.first() {
+            Some(&PUT) => codec::get_length_prefixed::<KEY>(payload, 9).and_then(|(key, end)| {
+                VALUE::decode(&payload[end..]).map(|value| LogRecord::Put(key, value))
+            }),
+            Some(&DELETE) => KEY::decode(&payload[9..]).map(LogRecord::Delete),
+            Some(&MERGE) => codec::get_length_prefixed::<KEY>(payload, 9).and_then(|(key, end)| {
+                VALUE::decode(&payload[end..]).map(|operand| LogRecord::Merge(key, operand))
+            }),
+            Some(&BATCH) => return WriteAheadLog::decode_batch(payload, sequence),
+            _ => None,
+        };
+
+        record.map(|record| vec![(sequence, record)])
+    }
+
+    fn decode_batch<KEY: Codec, VALUE: Codec>(
+        payload: &[u8],
+        sequence: u64,
+    ) -> Option<Vec<(u64, LogRecord<KEY, VALUE>)>> {
+        let count = codec::get_u32(payload, 9)? as u64;
+        let mut offset = 13;
+        let mut writes = Vec::new();
+
+        for sequence in sequence..sequence.checked_add(count)? {
+            let kind = *payload.get(offset)?;
+            let (key, end) = codec::get_length_prefixed::<KEY>(payload, offset + 1)?;
+
+            let record = match kind {
+                PUT => {
+                    let (value, end) = codec::get_length_prefixed::<VALUE>(payload, end)?;
+                    offset = end;
+                    LogRecord::Put(key, value)
+                }
+                DELETE => {
+                    offset = end;
+                    LogRecord::Delete(key)
+                }
+                MERGE => {
+                    let (operand, end) = codec::get_length_prefixed::<VALUE>(payload, end)?;
+                    offset = end;
+                    LogRecord::Merge(key, operand)
+                }
+                _ => return None,
+            };
+
+            writes.push((sequence, record));
+        }
+
+        (offset == payload.len()).then_some(writes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lsm::temp_dir::TempDir;
+    use crate::symbol_table::red_black_bst::RedBlackBST;
+    use crate::symbol_table::ST;
+
+    // rebuilds the symbol table the puts and deletes of a log describe, merge operands need
+    // the merge operator of a database and are left out
+    fn recover<KEY, VALUE>(path: &Path) -> Result<RedBlackBST<KEY, VALUE>>
+    where
+        KEY: Codec + Ord + Clone,
+        VALUE: Codec + Clone,
+    {
+        let mut tree = RedBlackBST::new();
+
+        WriteAheadLog::replay(path, |_, record| match record {
+            LogRecord::Put(key, value) => tree.put(key, value),
+            LogRecord::Delete(key) => tree.delete(&key),
+            LogRecord::Merge(..) => (),
+        })?;
+
+        Ok(tree)
+    }
+
+    fn write_words(path: &Path) {
+        let log = &mut WriteAheadLog::open(path).unwrap();
+
+        for (position, key) in "S E A R C H E X A M P L E".split(" ").enumerate() {
+            log.append_put(position as u64 + 1, &String::from(key), &(position as u64))
+                .unwrap();
+        }
+
+        log.append_delete(14, &String::from("X")).unwrap();
+        log.sync().unwrap();
+    }
+
+    #[test]
+    fn recover_replays_puts_and_deletes() {
+        // arrange
+        let dir = TempDir::new("wal-replay").unwrap();
+        let path = dir.path().join("000001.log");
+        write_words(&path);
+
+        // act
+        let st: RedBlackBST<String, u64> = recover(&path).unwrap();
+
+        // assert
+        assert_eq!(st.size(), 9);
+        assert_eq!(st.get(&String::from("E")), Some(&12));
+        assert_eq!(st.get(&String::from("S")), Some(&0));
+        assert!(!st.contains(&String::from("X")));
+    }
+
+    #[test]
+    fn recover_reports_undecodable_records() {
+        // arrange
+        let dir = TempDir::new("wal-undecodable").unwrap();
+        let path = dir.path().join("000001.log");
+        write_words(&path);
+        let offset = fs::metadata(&path).unwrap().len();
+
+        let log = &mut WriteAheadLog::open(&path).unwrap();
+        log.append(&[9, 9, 9]).unwrap();
+        log.append_delete(15, &String::from("S")).unwrap();
+
+        // act
+        let recovered = recover::<String, u64>(&path);
+
+        // assert
+        match recovered {
+            Err(Error::Corruption { file, offset: at }) => {
+                assert_eq!(file, path);
+                assert_eq!(at, offset);
+            }
+            _ => panic!("undecodable record accepted"),
+        }
+    }
+
+    #[test]
+    fn batches_replay_whole_or_not_at_all() {
+        // arrange
+        let dir = TempDir::new("wal-batch").unwrap();
+        let path = dir.path().join("000001.log");
+        write_words(&path);
+
+        let mut batch = WriteBatch::new();
+        batch.put(String::from("Y"), 1_u64);
+        batch.delete(String::from("S"));
+        batch.put(String::from("Z"), 2_u64);
+
+        let log = &mut WriteAheadLog::open(&path).unwrap();
+        log.append_batch(15, &batch).unwrap();
+        let batch_end = fs::metadata(&path).unwrap().len();
+        log.append_batch(18, &batch).unwrap();
+
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(batch_end + 20).unwrap();
+
+        // act
+        let mut sequences = Vec::new();
+        WriteAheadLog::replay(&path, |sequence, _: LogRecord<String, u64>| {
+            sequences.push(sequence)
+        })
+        .unwrap();
+        let st: RedBlackBST<String, u64> = recover(&path).unwrap();
+
+        // assert
+        assert_eq!(sequences[13..], [14, 15, 16, 17]);
+        assert_eq!(st.size(), 10);
+        assert!(!st.contains(&String::from("S")));
+        assert_eq!(st.get(&String::from("Z")), Some(&2));
+        assert_eq!(fs::metadata(&path).unwrap().len(), batch_end);
+    }
+
+    #[test]
+    fn recover_missing_log_is_empty() {
+        let dir = TempDir::new("wal-missing").unwrap();
+
+        let st: RedBlackBST<String, u64> = recover(&dir.path().join("000001.log")).unwrap();
+
+        assert!(st.is_empty());
+    }
+
+    #[test]
+    fn recover_truncates_torn_tail_and_accepts_new_records() {
+        // arrange
+        let dir = TempDir::new("wal-torn").unwrap();
+        let path = dir.path().join("000001.log");
+        write_words(&path);
+        let intact_length = fs::metadata(&path).unwrap().len();
+
+        let log = &mut WriteAheadLog::open(&path).unwrap();
+        log.append_put(15, &String::from("Z"), &99_u64).unwrap();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(intact_length + 10).unwrap();
+
+        // act
+        let torn: RedBlackBST<String, u64> = recover(&path).unwrap();
+        let truncated_length = fs::metadata(&path).unwrap().len();
+
+        WriteAheadLog::open(&path)
+            .unwrap()
+            .append_put(15, &String::from("Y"), &7_u64)
+            .unwrap();
+        let st: RedBlackBST<String, u64> = recover(&path).unwrap();
+
+        // assert
+        assert_eq!(torn.size(), 9);
+        assert!(!torn.contains(&String::from("Z")));
+        assert_eq!(truncated_length, intact_length);
+        assert_eq!(st.get(&String::from("Y")), Some(&7));
+        assert_eq!(st.size(), 10);
+    }
+
+    #[test]
+    fn recover_reports_corrupted_records_and_keeps_the_log() {
+        // arrange
+        let dir = TempDir::new("wal-corrupted").unwrap();
+        let path = dir.path().join("000001.log");
+        write_words(&path);
+        let first_length = {
+            let bytes = fs::read(&path).unwrap();
+            HEADER_SIZE + codec::get_u32(&bytes, 4).unwrap() as usize
+        };
+
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[first_length + HEADER_SIZE] ^= 0xff; // payload of the second record
+        fs::write(&path, &bytes).unwrap();
+
+        // act
+        let recovered = recover::<String, u64>(&path);
+
+        // assert
+        match recovered {
+            Err(Error::Corruption { file, offset }) => {
+                assert_eq!(file, path);
+                assert_eq!(offset, first_length as u64);
+            }
+            _ => panic!("corrupted record in the middle of the log accepted"),
+        }
+        assert_eq!(fs::read(&path).unwrap(), bytes);
+    }
+
+    #[test]
+    fn recover_truncates_a_damaged_last_record_and_what_a_crash_left_after_it() {
+        // arrange: the last record is written and then damaged the ways a crash leaves
+        // it, zeros passing the checksum of an empty payload
+        let dir = TempDir::new("wal-damaged-tail").unwrap();
+
+        for damage in ["flipped", "zeroed", "garbage"] {
+            let path = dir.path().join(damage);
+            write_words(&path);
+            let intact_length = fs::metadata(&path).unwrap().len() as usize;
+
+            WriteAheadLog::open(&path)
+                .unwrap()
+                .append_put(15, &String::from("Z"), &99_u64)
+                .unwrap();
+            let mut bytes = fs::read(&path).unwrap();
+            let tail = &mut bytes[intact_length..];
+
+            match damage {
+                "flipped" => *tail.last_mut().unwrap() ^= 0xff,
+                "zeroed" => tail.fill(0),
+                _ => (0..tail.len()).for_each(|i| tail[i] = (i as u8).wrapping_mul(37) ^ 0x5a),
+            }
+            fs::write(&path, &bytes).unwrap();
+
+            // act
+            let st: RedBlackBST<String, u64> = recover(&path).unwrap();
+
+            // assert
+            assert_eq!(st.size(), 9, "{} tail", damage);
+            assert!(!st.contains(&String::from("Z")), "{} tail", damage);
+            assert_eq!(fs::metadata(&path).unwrap().len() as usize, intact_length);
+        }
+    }
+}
@@ -1,35 +1,229 @@
+mod error;
+mod lsm;
+mod priority_queue;
 mod stack;
 mod symbol_table;
 
+use crate::error::Error;
+use crate::lsm::cache::BlockCache;
+use crate::lsm::compaction::Leveled;
+use crate::lsm::db::{LsmDb, Options};
+use crate::lsm::merge_operator::U64Add;
+use crate::lsm::sstable::reader::Table;
+use crate::lsm::sstable::writer::write_table;
+use crate::lsm::sstable::TableOptions;
+use crate::lsm::temp_dir::TempDir;
 use crate::symbol_table::bst::BST;
 use crate::symbol_table::red_black_bst::RedBlackBST;
 use crate::symbol_table::test_client::frequency_counter::FrequencyCounter;
-use crate::symbol_table::ST;
+use crate::symbol_table::{KvStore, ReadOnlyST, ST};
 
 use std::time::Instant;
 
-fn main() {
+// how the benchmark counts the words of a file into a symbol table
+type Build<T> = fn(&mut T, &str, usize) -> Result<u64, Error>;
+
+fn main() -> Result<(), Error> {
+    let path = "data/leipzig1M.txt";
+
     let mut red_black: RedBlackBST<String, u64> = RedBlackBST::new();
-    instrument(&mut red_black, "Red Back BST", "data/leipzig1M.txt");
+    instrument(
+        &mut red_black,
+        "Red Back BST",
+        path,
+        FrequencyCounter::build_in_place,
+    )?;
+    instrument_table(&red_black)?;
+
+    let mut lsm: LsmDb<String, u64> = LsmDb::temporary(Options::default())?;
+    instrument(
+        &mut lsm,
+        "LSM DB (size-tiered)",
+        path,
+        FrequencyCounter::build,
+    )?;
+    fold_case(&mut lsm)?;
+
+    let mut lsm: LsmDb<String, u64> = LsmDb::temporary(Options::default())?;
+    lsm.set_merge_operator(U64Add);
+    instrument(
+        &mut lsm,
+        "LSM DB (size-tiered, merge operator)",
+        path,
+        FrequencyCounter::build_merged,
+    )?;
+
+    let leveled = Options {
+        compaction: Box::new(Leveled::default()),
+        ..Options::default()
+    };
+    let mut lsm: LsmDb<String, u64> = LsmDb::temporary(leveled)?;
+    instrument(&mut lsm, "LSM DB (leveled)", path, FrequencyCounter::build)?;
 
     let mut bst: BST<String, u64> = BST::new();
-    instrument(&mut bst, "BST", "data/leipzig1M.txt");
+    instrument(&mut bst, "BST", path, FrequencyCounter::build_in_place)
 }
 
-fn instrument(st: &mut impl ST<String, u64>, name: &str, path: &str) {
+// writes the counts of the tree to a table file, then reads them back from it
+fn instrument_table(st: &RedBlackBST<String, u64>) -> Result<(), Error> {
+    println!("SSTable (written from the Red Back BST)");
+    println!("-----------");
+
+    let dir = TempDir::new("benchmark-table")?;
+    let path = dir.path().join("000001.sst");
+    let write_instant = Instant::now();
+
+    write_table(&path, st, &TableOptions::default())?;
+
+    let write_st = write_instant.elapsed();
+    let read_instant = Instant::now();
+
+    let cache = BlockCache::new(Options::default().block_cache_size);
+    let table: Table<String, u64> = Table::open(&path, &cache)?;
+    let mut max = String::new();
+    let mut max_count = 0;
+
+    for entry in table.iter() {
+        let (word, count) = entry?;
+
+        if count > max_count {
+            max = word;
+            max_count = count;
+        }
+    }
+
+    let word = String::from("table");
+
+    println!(
+        "most used word is '{}': {}",
+        max,
+        table.get(&max)?.unwrap_or(0)
+    );
+    println!(
+        "'{}' is counted: {}, floor: {:?}, ceiling: {:?}",
+        word,
+        table.contains(&word)?,
+        table.floor(&word)?,
+        table.ceiling(&word)?
+    );
+
+    // rev yields the greatest words first, the first of them being the table max
+    let last_words: Vec<&String> = st.rev().take(3).map(|(word, _)| word).collect();
+
+    println!(
+        "last words of the tree: {:?}, table ends with: {:?}",
+        last_words,
+        table.max()
+    );
+
+    if !table.is_empty() {
+        println!(
+            "words from '{}' to '{}': {}, from 'a' to 'b': {}",
+            table.min().map_or("", String::as_str),
+            table.max().map_or("", String::as_str),
+            table.size(),
+            table
+                .keys_in_range(&String::from("a"), &String::from("b"))?
+                .len()
+        );
+    }
+
+    println!("Elapsed time to write table file: {:.2?}", write_st);
+    println!(
+        "Elapsed time to read table file: {:.2?}",
+        read_instant.elapsed()
+    );
+    println!();
+
+    Ok(())
+}
+
+// folds capitalized words into lowercase ones, comparing a count before and after with
+// a snapshot taken before, then flushes and compacts what the fold rewrote
+fn fold_case(db: &mut LsmDb<String, u64>) -> Result<(), Error> {
+    println!("LSM DB (size-tiered, case folding)");
+    println!("-----------");
+
+    let instant = Instant::now();
+    let word = String::from("the");
+    let snapshot = db.snapshot();
+
+    let folded = FrequencyCounter::fold_case(db, &snapshot)?;
+
+    println!("capitalized words folded: {}", folded);
+    println!(
+        "'{}' before folding: {}, after: {}",
+        word,
+        db.get_at(&word, &snapshot)?.unwrap_or(0),
+        KvStore::get(db, &word)?.map_or(0, |frequency| *frequency)
+    );
+
+    // versions only the snapshot still sees may go once it is dropped
+    drop(snapshot);
+    db.flush()?;
+    db.compact()?;
+
+    println!(
+        "Elapsed time to fold, flush and compact: {:.2?}",
+        instant.elapsed()
+    );
+    println!();
+
+    Ok(())
+}
+
+// storage statistics reported along with the benchmark, none for in-memory tables
+trait Stats {
+    fn stats(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl Stats for RedBlackBST<String, u64> {}
+
+impl Stats for BST<String, u64> {}
+
+impl Stats for LsmDb<String, u64> {
+    fn stats(&self) -> Vec<String> {
+        let filter = self.filter_stats();
+        let cache = self.cache_stats();
+
+        vec![
+            format!(
+                "bloom filter hits: {}, misses: {}, false positives: {}",
+                filter.hits, filter.misses, filter.false_positives
+            ),
+            format!(
+                "block cache hit rate: data {:.1}%, index {:.1}%, filter {:.1}%, usage: {} KiB",
+                cache.data.hit_rate() * 100.0,
+                cache.index.hit_rate() * 100.0,
+                cache.filter.hit_rate() * 100.0,
+                cache.usage / 1024
+            ),
+        ]
+    }
+}
+
+fn instrument<T: KvStore<String, u64> + Stats>(
+    st: &mut T,
+    name: &str,
+    path: &str,
+    build: Build<T>,
+) -> Result<(), Error> {
     println!("{}", name);
     println!("-----------");
 
     let main_instant = Instant::now();
 
-    let count = FrequencyCounter::build(st, path, 1);
+    let count = build(st, path, 1)?;
 
     let load_st = main_instant.elapsed();
     let read_instant = Instant::now();
 
-    let max = FrequencyCounter::find_max(st);
+    let max = FrequencyCounter::find_max(st)?;
+    let frequency = st.get(&max)?.map_or(0, |frequency| *frequency);
 
-    println!("most used word is '{}': {}", max, st.get(&max).unwrap());
+    println!("most used word is '{}': {}", max, frequency);
 
     let read_st = read_instant.elapsed();
     let total = main_instant.elapsed();
@@ -48,7 +242,14 @@ fn instrument(st: &mut impl ST<String, u64>, name: &str, path: &str) {
         (1000_u128 * (count as u128) / read_st.as_millis())
     );
     println!("count words: {:.2?}", count);
-    println!("nodes (dinstinct words): {:.2?}", st.size());
+    println!("nodes (dinstinct words): {:.2?}", st.size()?);
+
+    for line in st.stats() {
+        println!("{}", line);
+    }
+
     println!("Total time: {:.2?}", total);
     println!();
+
+    Ok(())
 }
@@ -0,0 +1,151 @@
+// binary heap where pop always returns the smallest item
+pub struct MinPriorityQueue<T>
+where
+    T: Ord,
+{
+    items: Vec<T>,
+}
+
+#[allow(dead_code)]
+impl<T> MinPriorityQueue<T>
+where
+    T: Ord,
+{
+    pub fn new() -> MinPriorityQueue<T> {
+        MinPriorityQueue { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, value: T) {
+        self.items.push(value);
+        self.swim(self.items.len() - 1);
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        if self.items.is_empty() {
+            return None;
+        }
+
+        let last = self.items.len() - 1;
+        self.items.swap(0, last);
+        let min = self.items.pop();
+        self.sink(0);
+
+        min
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.items.first()
+    }
+
+    pub fn size(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    // moves the item up while it is smaller than its parent
+    fn swim(&mut self, mut position: usize) {
+        while position > 0 {
+            let parent = (position - 1) / 2;
+
+            if self.items[position] >= self.items[parent] {
+                break;
+            }
+
+            self.items.swap(position, parent);
+            position = parent;
+        }
+    }
+
+    // moves the item down while it is greater than its smallest child
+    fn sink(&mut self, mut position: usize) {
+        loop {
+            let left = 2 * position + 1;
+
+            if left >= self.items.len() {
+                break;
+            }
+
+            let right = left + 1;
+            let child = if right < self.items.len() && self.items[right] < self.items[left] {
+                right
+            } else {
+                left
+            };
+
+            if self.items[position] <= self.items[child] {
+                break;
+            }
+
+            self.items.swap(position, child);
+            position = child;
+        }
+    }
+}
+
+impl<T> Default for MinPriorityQueue<T>
+where
+    T: Ord,
+{
+    fn default() -> MinPriorityQueue<T> {
+        MinPriorityQueue::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_queue() {
+        let queue = &mut MinPriorityQueue::<u64>::new();
+
+        assert!(queue.is_empty());
+        assert_eq!(queue.peek(), None);
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn pop_returns_items_in_ascending_order() {
+        let queue = &mut MinPriorityQueue::new();
+
+        for key in "S E A R C H E X A M P L E".split(" ") {
+            queue.push(String::from(key));
+        }
+
+        assert_eq!(queue.size(), 13);
+        assert_eq!(queue.peek(), Some(&String::from("A")));
+
+        let mut popped = Vec::new();
+        while let Some(key) = queue.pop() {
+            popped.push(key);
+        }
+
+        assert_eq!(
+            popped,
+            vec!["A", "A", "C", "E", "E", "E", "H", "L", "M", "P", "R", "S", "X"]
+        );
+    }
+
+    #[test]
+    fn interleaved_push_and_pop_keep_heap_order() {
+        let queue = &mut MinPriorityQueue::new();
+
+        for i in 0..100_u64 {
+            queue.push((i * 37) % 101);
+
+            if i % 3 == 0 {
+                let min = queue.pop().unwrap();
+                assert!(queue.peek().is_none_or(|next| *next >= min));
+            }
+        }
+
+        let mut previous = 0;
+        while let Some(value) = queue.pop() {
+            assert!(value >= previous);
+            previous = value;
+        }
+    }
+}
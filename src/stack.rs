@@ -5,10 +5,7 @@ struct StackNode<T> {
 
 impl<T> StackNode<T> {
     pub fn new<S>(value: S, next: Option<StackNode<S>>) -> StackNode<S> {
-        let next = match next {
-            Some(node) => Some(Box::new(node)),
-            None => None,
-        };
+        let next = next.map(Box::new);
 
         StackNode { value, next }
     }
@@ -50,6 +47,13 @@ impl<T> Stack<T> {
     }
 }
 
+// pops nodes one by one, as dropping the root would drop the whole chain recursively
+impl<T> Drop for Stack<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,7 +62,7 @@ mod tests {
     fn empty_stack() {
         let stack = Stack::<String>::new::<String>();
 
-        assert_eq!(stack.has_value(), false);
+        assert!(!stack.has_value());
     }
 
     #[test]
@@ -67,7 +71,7 @@ mod tests {
 
         stack.push(String::from("first"));
 
-        assert_eq!(stack.has_value(), true);
+        assert!(stack.has_value());
     }
 
     #[test]
@@ -1,5 +1,10 @@
+use crate::error::Result;
+use crate::stack::Stack;
+use crate::symbol_table::entry::{self, Entry, InPlace};
+use crate::symbol_table::node::{Iter, TreeNode};
 use crate::symbol_table::ST;
-use std::{ops::Deref, usize};
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut, RangeBounds};
 
 pub struct Node<KEY, VALUE>
 where
@@ -27,6 +32,47 @@ where
     }
 }
 
+impl<KEY, VALUE> TreeNode for Node<KEY, VALUE>
+where
+    KEY: Ord,
+{
+    type Key = KEY;
+    type Value = VALUE;
+
+    fn leaf(key: KEY, value: VALUE) -> Node<KEY, VALUE> {
+        Node::<KEY, VALUE>::new(key, value, 1)
+    }
+
+    fn key(&self) -> &KEY {
+        &self.key
+    }
+
+    fn value(&self) -> &VALUE {
+        &self.value
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn left(&self) -> &Option<Self> {
+        &self.left
+    }
+
+    fn right(&self) -> &Option<Self> {
+        &self.right
+    }
+
+    fn value_mut(&mut self) -> &mut VALUE {
+        &mut self.value
+    }
+
+    fn size_and_children_mut(&mut self) -> (&mut usize, &mut Option<Self>, &mut Option<Self>) {
+        (&mut self.size, &mut *self.left, &mut *self.right)
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
 pub struct BST<KEY, VALUE>
 where
     KEY: Ord,
@@ -38,164 +84,221 @@ impl<KEY, VALUE> BST<KEY, VALUE>
 where
     KEY: Ord,
 {
+    // a present key is updated by the descent finding it, while a new one is inserted by a
+    // second descent to its position, which grows the sizes the first can't know will grow
     fn put_node(node: &mut Option<Node<KEY, VALUE>>, key: KEY, value: VALUE) {
-        if node.is_none() {
-            node.replace(Node::<KEY, VALUE>::new(key, value, 1));
-            return;
+        let mut current = &mut *node;
+        let mut position = 0;
+
+        while let Some(n) = current {
+            let left_count = BST::get_size(&n.left);
+
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => current = n.left.deref_mut(),
+                std::cmp::Ordering::Greater => {
+                    position += left_count + 1;
+                    current = n.right.deref_mut();
+                }
+                std::cmp::Ordering::Equal => {
+                    n.value = value;
+                    return;
+                }
+            }
         }
 
-        let node = node.as_mut().unwrap();
+        BST::insert_node(node, position, key, value);
+    }
 
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => BST::put_node(&mut node.left, key, value),
-            std::cmp::Ordering::Greater => BST::put_node(&mut node.right, key, value),
-            std::cmp::Ordering::Equal => node.value = value,
+    // the key must be absent and take position once inserted
+    fn insert_node(
+        node: &mut Option<Node<KEY, VALUE>>,
+        mut position: usize,
+        key: KEY,
+        value: VALUE,
+    ) -> &mut VALUE {
+        let mut node = node;
+
+        while let Some(n) = node {
+            let left_count = BST::get_size(&n.left);
+            n.size += 1;
+
+            if position <= left_count {
+                node = n.left.deref_mut();
+            } else {
+                position -= left_count + 1;
+                node = n.right.deref_mut();
+            }
         }
 
-        node.size = BST::get_size(&node.left) + BST::get_size(&node.right) + 1;
+        &mut node.insert(Node::<KEY, VALUE>::new(key, value, 1)).value
     }
 
-    fn get_size(node: &Box<Option<Node<KEY, VALUE>>>) -> usize {
-        match node.deref() {
+    fn get_size(node: &Option<Node<KEY, VALUE>>) -> usize {
+        match node {
             Some(n) => n.size,
             None => 0,
         }
     }
 
     fn get_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a VALUE> {
-        if node.is_none() {
-            return None;
-        }
-
-        let node = node.as_ref().unwrap();
+        let mut node = node;
 
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => BST::get_node(node.left.deref(), key),
-            std::cmp::Ordering::Greater => BST::get_node(node.right.deref(), key),
-            std::cmp::Ordering::Equal => Some(&node.value),
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => node = n.left.deref(),
+                std::cmp::Ordering::Greater => node = n.right.deref(),
+                std::cmp::Ordering::Equal => return Some(&n.value),
+            }
         }
+
+        None
     }
 
     fn min_node(node: &Option<Node<KEY, VALUE>>) -> Option<&KEY> {
-        if node.is_none() {
-            return None;
-        }
-
-        let node = node.as_ref().unwrap();
+        let mut node = node.as_ref()?;
 
-        if node.left.is_none() {
-            return Some(&node.key);
+        while let Some(left) = node.left.deref() {
+            node = left;
         }
 
-        BST::min_node(&node.left)
+        Some(&node.key)
     }
 
     fn max_node(node: &Option<Node<KEY, VALUE>>) -> Option<&KEY> {
-        if node.is_none() {
-            return None;
-        }
+        let mut node = node.as_ref()?;
 
-        let node = node.as_ref().unwrap();
-
-        if node.right.is_none() {
-            return Some(&node.key);
+        while let Some(right) = node.right.deref() {
+            node = right;
         }
 
-        BST::max_node(&node.right)
+        Some(&node.key)
     }
 
     pub fn floor_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a KEY> {
-        if node.is_none() {
-            return None;
+        let mut node = node;
+        let mut floor = None; // greatest key smaller than key seen on the way down
+
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => node = n.left.deref(),
+                std::cmp::Ordering::Greater => {
+                    floor = Some(&n.key);
+                    node = n.right.deref();
+                }
+                std::cmp::Ordering::Equal => return Some(&n.key),
+            }
         }
 
-        let node = node.as_ref().unwrap();
-
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => BST::floor_node(node.left.deref(), key),
-            std::cmp::Ordering::Greater => match BST::floor_node(node.right.deref(), key) {
-                Some(v) => Some(v),
-                None => Some(&node.key),
-            },
-            std::cmp::Ordering::Equal => Some(&node.key),
-        }
+        floor
     }
 
     fn ceiling_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a KEY> {
-        if node.is_none() {
-            return None;
+        let mut node = node;
+        let mut ceiling = None; // smallest key greater than key seen on the way down
+
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => {
+                    ceiling = Some(&n.key);
+                    node = n.left.deref();
+                }
+                std::cmp::Ordering::Greater => node = n.right.deref(),
+                std::cmp::Ordering::Equal => return Some(&n.key),
+            }
         }
 
-        let node = node.as_ref().unwrap();
-
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => match BST::ceiling_node(node.left.deref(), key) {
-                Some(v) => Some(v),
-                None => Some(&node.key),
-            },
-            std::cmp::Ordering::Greater => BST::ceiling_node(node.right.deref(), key),
-            std::cmp::Ordering::Equal => Some(&node.key),
-        }
+        ceiling
     }
 
-    fn select_node(node: &Option<Node<KEY, VALUE>>, position: usize) -> Option<&KEY> {
-        let node = node.as_ref().unwrap();
-        let left_count = BST::get_size(&node.left);
+    // position must be less than the size of the subtree
+    fn select_node(node: &Option<Node<KEY, VALUE>>, mut position: usize) -> Option<&KEY> {
+        let mut node = node.as_ref().unwrap();
+
+        loop {
+            let left_count = BST::get_size(&node.left);
 
-        match position.cmp(&left_count) {
-            std::cmp::Ordering::Less => BST::select_node(node.left.deref(), position),
-            std::cmp::Ordering::Greater => {
-                BST::select_node(node.right.deref(), position - left_count - 1)
+            match position.cmp(&left_count) {
+                std::cmp::Ordering::Less => node = node.left.deref().as_ref().unwrap(),
+                std::cmp::Ordering::Greater => {
+                    position -= left_count + 1;
+                    node = node.right.deref().as_ref().unwrap();
+                }
+                std::cmp::Ordering::Equal => return Some(&node.key),
             }
-            std::cmp::Ordering::Equal => Some(&node.key),
         }
     }
 
-    fn rank_node(node: &Option<Node<KEY, VALUE>>, key: &KEY, position: usize) -> Option<usize> {
-        if node.is_none() {
-            return None;
+    fn update_size(node: &mut Node<KEY, VALUE>) {
+        node.size = BST::get_size(&node.left) + BST::get_size(&node.right) + 1;
+    }
+
+    // detaches the minimum node of the subtree, linking its right child in its place
+    fn delete_min_node(node: &mut Option<Node<KEY, VALUE>>) -> Option<Node<KEY, VALUE>> {
+        let mut node = node;
+
+        while node.as_ref()?.left.is_some() {
+            let n = node.as_mut().unwrap();
+            n.size -= 1;
+            node = n.left.deref_mut();
         }
 
-        let node = node.as_ref().unwrap();
+        let mut min = node.take()?;
+        *node = min.right.take();
+        Some(min)
+    }
 
-        let left_count = BST::get_size(&node.left);
+    // detaches the maximum node of the subtree, linking its left child in its place
+    fn delete_max_node(node: &mut Option<Node<KEY, VALUE>>) -> Option<Node<KEY, VALUE>> {
+        let mut node = node;
 
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => BST::rank_node(node.left.deref(), key, position),
-            std::cmp::Ordering::Greater => {
-                BST::rank_node(node.right.deref(), key, position + left_count + 1)
-            }
-            std::cmp::Ordering::Equal => Some(position + left_count),
+        while node.as_ref()?.right.is_some() {
+            let n = node.as_mut().unwrap();
+            n.size -= 1;
+            node = n.right.deref_mut();
         }
+
+        let mut max = node.take()?;
+        *node = max.left.take();
+        Some(max)
     }
 
-    fn keys_node<'a>(
-        result: &mut Vec<&'a KEY>,
-        node: &'a Option<Node<KEY, VALUE>>,
-        min_key: &KEY,
-        max_key: &KEY,
-    ) {
-        if node.is_none() {
+    // Hibbard deletion: a node with two children is replaced by its successor
+    fn delete_node(node: &mut Option<Node<KEY, VALUE>>, key: &KEY) {
+        if BST::get_node(node, key).is_none() {
             return;
         }
 
-        let node = node.as_ref().unwrap();
+        let mut node = node;
 
-        if &node.key > min_key {
-            BST::keys_node(result, &node.left.as_ref(), min_key, max_key);
-        }
+        while key != &node.as_ref().unwrap().key {
+            let n = node.as_mut().unwrap();
+            n.size -= 1;
 
-        if &node.key >= min_key && &node.key <= max_key {
-            result.push(&node.key);
+            if key < &n.key {
+                node = n.left.deref_mut();
+            } else {
+                node = n.right.deref_mut();
+            }
         }
 
-        if &node.key < max_key {
-            BST::keys_node(result, &node.right.as_ref(), min_key, max_key);
-        }
+        let mut deleted = node.take().unwrap();
+
+        *node = match (deleted.left.take(), deleted.right.take()) {
+            (left, None) => left,
+            (None, right) => right,
+            (left, right) => {
+                let mut right = right;
+                let mut successor = BST::delete_min_node(&mut right).unwrap();
+                *successor.left = left;
+                *successor.right = right;
+                BST::update_size(&mut successor);
+                Some(successor)
+            }
+        };
     }
 
     // don't take this too seriously
-    #[allow(dead_code)]
+    #[cfg(test)]
     pub fn draw_node(
         node: &Option<Node<String, VALUE>>,
         position: usize,
@@ -207,7 +310,7 @@ where
         }
 
         let node = node.as_ref().unwrap();
-        let width = 200 / (2 as usize).pow(level as u32);
+        let width = 200 / 2_usize.pow(level as u32);
         let padding = width * position + width / 2;
 
         if result.len() <= level {
@@ -296,24 +399,172 @@ where
     }
 
     fn rank(&self, key: &KEY) -> Option<usize> {
-        BST::rank_node(&self.root, key, 0)
+        let mut node = &self.root;
+        let mut position = 0;
+
+        while let Some(n) = node {
+            let left_count = BST::get_size(&n.left);
+
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => node = n.left.deref(),
+                std::cmp::Ordering::Greater => {
+                    position += left_count + 1;
+                    node = n.right.deref();
+                }
+                std::cmp::Ordering::Equal => return Some(position + left_count),
+            }
+        }
+
+        None
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        Iter::new(&self.root, .., false)
+    }
+
+    fn range<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        Iter::new(&self.root, range, false)
     }
 
-    fn keys_in_range(&self, min_key: &KEY, max_key: &KEY) -> Vec<&KEY> {
-        let mut keys = Vec::new();
-        BST::keys_node(&mut keys, &self.root, &min_key, &max_key);
-        return keys;
+    fn rev<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        Iter::new(&self.root, .., true)
+    }
+
+    fn delete(&mut self, key: &KEY) {
+        BST::delete_node(&mut self.root, key);
+    }
+
+    fn delete_min(&mut self) {
+        BST::delete_min_node(&mut self.root);
+    }
+
+    fn delete_max(&mut self) {
+        BST::delete_max_node(&mut self.root);
+    }
+}
+
+// Dropping the root would drop every node recursively, which overflows the stack once
+// sorted input has degenerated the tree into a list, so nodes are unlinked one by one.
+impl<KEY, VALUE> Drop for BST<KEY, VALUE>
+where
+    KEY: Ord,
+{
+    fn drop(&mut self) {
+        let mut stack = Stack::<Node<KEY, VALUE>>::new::<Node<KEY, VALUE>>();
+
+        if let Some(root) = self.root.take() {
+            stack.push(root);
+        }
+
+        while let Some(mut node) = stack.pop() {
+            if let Some(left) = node.left.take() {
+                stack.push(left);
+            }
+
+            if let Some(right) = node.right.take() {
+                stack.push(right);
+            }
+        }
+    }
+}
+
+impl<KEY, VALUE> super::KvStore<KEY, VALUE> for BST<KEY, VALUE>
+where
+    KEY: Ord + Clone,
+    VALUE: Clone,
+{
+    fn put(&mut self, key: KEY, value: VALUE) -> Result<()> {
+        ST::put(self, key, value);
+        Ok(())
+    }
+
+    fn get(&self, key: &KEY) -> Result<Option<Cow<'_, VALUE>>> {
+        Ok(ST::get(self, key).map(Cow::Borrowed))
+    }
+
+    fn delete(&mut self, key: &KEY) -> Result<()> {
+        ST::delete(self, key);
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize> {
+        Ok(ST::size(self))
+    }
+
+    fn min(&self) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::min(self).map(Cow::Borrowed))
+    }
+
+    fn max(&self) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::max(self).map(Cow::Borrowed))
+    }
+
+    fn floor(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::floor(self, key).map(Cow::Borrowed))
+    }
+
+    fn ceiling(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::ceiling(self, key).map(Cow::Borrowed))
+    }
+
+    fn select(&self, position: usize) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::select(self, position).map(Cow::Borrowed))
+    }
+
+    fn rank(&self, key: &KEY) -> Result<Option<usize>> {
+        Ok(ST::rank(self, key))
+    }
+
+    fn scan<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = Result<(Cow<'a, KEY>, Cow<'a, VALUE>)>>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        ST::range(self, range).map(|(key, value)| Ok((Cow::Borrowed(key), Cow::Borrowed(value))))
+    }
+}
+
+impl<KEY, VALUE> InPlace<KEY, VALUE> for BST<KEY, VALUE>
+where
+    KEY: Ord,
+{
+    fn entry(&mut self, key: KEY) -> Entry<'_, KEY, VALUE> {
+        entry::descend(&mut self.root, key, None)
+    }
+
+    fn get_mut(&mut self, key: &KEY) -> Option<&mut VALUE> {
+        entry::find_mut(&mut self.root, key)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::symbol_table::test_client::symbol_table_integration::run_tests;
+    use crate::symbol_table::test_client::symbol_table_integration::{run_entry_tests, run_tests};
+    use std::thread;
 
     #[test]
     fn run_integration_tests() {
         run_tests::<BST<String, String>>();
+        run_entry_tests::<BST<String, String>>();
     }
 
     #[test]
@@ -361,4 +612,72 @@ mod tests {
             println!("{}", line);
         }
     }
+
+    #[test]
+    fn sorted_keys_do_not_overflow_the_stack() {
+        // arrange: inserting sorted keys walks the whole list each time, so instead of a
+        // million keys on the default stack, fewer keys on a stack far too small to recurse
+        let total = 10_000;
+        let keys: Vec<u64> = (0..total).collect();
+
+        // act
+        let st = thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(move || {
+                let mut st = BST::<u64, u64>::new();
+
+                for key in keys {
+                    st.put(key, key);
+                }
+
+                assert_eq!(st.size(), total as usize);
+                assert_eq!(st.get(&(total - 1)), Some(&(total - 1)));
+                assert_eq!(st.max(), Some(&(total - 1)));
+                assert_eq!(st.floor(&total), Some(&(total - 1)));
+                assert_eq!(st.select(total as usize - 1), Some(&(total - 1)));
+                assert_eq!(st.rank(&(total - 1)), Some(total as usize - 1));
+                assert_eq!(st.rev().next(), Some((&(total - 1), &(total - 1))));
+
+                st.delete(&(total / 2));
+                st.delete_max();
+                st.delete_min();
+                assert_eq!(st.size(), total as usize - 3);
+                assert_eq!(st.iter().count(), total as usize - 3);
+            })
+            .unwrap()
+            .join();
+
+        // assert
+        assert!(st.is_ok());
+    }
+    #[test]
+    fn million_sorted_keys_do_not_overflow_the_stack() {
+        // arrange: a million puts of sorted keys take quadratic time, so the list they
+        // would build is assembled directly, each key the right child of the one before
+        let total = 1_000_000;
+        let mut root = None;
+
+        for key in (0..total).rev() {
+            let mut node = Node::<u64, u64>::new(key, key, (total - key) as usize);
+            *node.right = root;
+            root = Some(node);
+        }
+
+        let mut st = BST { root };
+
+        // act
+        st.put(total, total);
+        st.delete(&(total / 2));
+        st.delete_max();
+        st.delete_min();
+
+        // assert
+        assert_eq!(st.size(), total as usize - 2);
+        assert_eq!(st.get(&(total - 1)), Some(&(total - 1)));
+        assert_eq!(st.floor(&total), Some(&(total - 1)));
+        assert_eq!(st.select(total as usize - 3), Some(&(total - 1)));
+        assert_eq!(st.rank(&(total - 1)), Some(total as usize - 3));
+        assert_eq!(st.rev().next(), Some((&(total - 1), &(total - 1))));
+        assert_eq!(st.iter().count(), total as usize - 2);
+    }
 }
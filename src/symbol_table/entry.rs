@@ -0,0 +1,157 @@
+use crate::symbol_table::node::TreeNode;
+use std::cmp::Ordering;
+
+// Symbol tables whose values can be updated in place. An entry takes one mutable descent,
+// ending at the node holding the key or at the empty child it would be inserted into.
+pub trait InPlace<KEY, VALUE> {
+    fn entry(&mut self, key: KEY) -> Entry<'_, KEY, VALUE>;
+    fn get_mut(&mut self, key: &KEY) -> Option<&mut VALUE>;
+}
+
+// view of a key of a tree, modeled on std::collections::btree_map::Entry
+pub enum Entry<'a, KEY, VALUE> {
+    Occupied(OccupiedEntry<'a, VALUE>),
+    Vacant(VacantEntry<'a, KEY, VALUE>),
+}
+
+pub struct OccupiedEntry<'a, VALUE> {
+    value: &'a mut VALUE,
+}
+
+pub struct VacantEntry<'a, KEY, VALUE> {
+    key: KEY,
+    slot: &'a mut dyn Slot<KEY, VALUE>,
+    sizes: Vec<&'a mut usize>, // of the nodes above the slot, which grow with the insert
+    position: usize,           // the key takes once inserted
+    inserted: Option<&'a mut Option<usize>>, // records position for trees rebalancing later
+}
+
+// empty child a vacant entry inserts into
+trait Slot<KEY, VALUE> {
+    fn fill(&mut self, key: KEY, value: VALUE) -> &mut VALUE;
+}
+
+impl<NODE: TreeNode> Slot<NODE::Key, NODE::Value> for Option<NODE> {
+    fn fill(&mut self, key: NODE::Key, value: NODE::Value) -> &mut NODE::Value {
+        self.insert(NODE::leaf(key, value)).value_mut()
+    }
+}
+
+// Descends from root to the entry of key. Each node is compared through a shared borrow
+// before borrowing it mutably to go on, so the slot the descent ends at can be lent to
+// either kind of entry. When given, inserted receives the position of an inserted key.
+pub fn descend<'a, NODE: TreeNode>(
+    root: &'a mut Option<NODE>,
+    key: NODE::Key,
+    inserted: Option<&'a mut Option<usize>>,
+) -> Entry<'a, NODE::Key, NODE::Value> {
+    let mut slot = root;
+    let mut sizes = Vec::new();
+    let mut position = 0;
+
+    while let Some(ordering) = compare(slot, &key) {
+        let (size, left, right) = slot.as_mut().unwrap().size_and_children_mut();
+        sizes.push(size);
+
+        if ordering == Ordering::Less {
+            slot = left;
+        } else {
+            position += left.as_ref().map_or(0, NODE::size) + 1;
+            slot = right;
+        }
+    }
+
+    if slot.is_some() {
+        return Entry::Occupied(OccupiedEntry {
+            value: slot.as_mut().unwrap().value_mut(),
+        });
+    }
+
+    Entry::Vacant(VacantEntry {
+        key,
+        slot,
+        sizes,
+        position,
+        inserted,
+    })
+}
+
+pub fn find_mut<'a, NODE: TreeNode>(
+    root: &'a mut Option<NODE>,
+    key: &NODE::Key,
+) -> Option<&'a mut NODE::Value> {
+    let mut slot = root;
+
+    while let Some(ordering) = compare(slot, key) {
+        let (_, left, right) = slot.as_mut().unwrap().size_and_children_mut();
+
+        slot = if ordering == Ordering::Less {
+            left
+        } else {
+            right
+        };
+    }
+
+    slot.as_mut().map(NODE::value_mut)
+}
+
+// how key compares to the node in slot, none once the slot is empty or holds the key
+fn compare<NODE: TreeNode>(slot: &Option<NODE>, key: &NODE::Key) -> Option<Ordering> {
+    slot.as_ref()
+        .map(|node| key.cmp(node.key()))
+        .filter(|ordering| ordering.is_ne())
+}
+
+impl<'a, KEY, VALUE> Entry<'a, KEY, VALUE> {
+    pub fn or_insert(self, default: VALUE) -> &'a mut VALUE {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> VALUE) -> &'a mut VALUE {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn and_modify(mut self, modify: impl FnOnce(&mut VALUE)) -> Entry<'a, KEY, VALUE> {
+        if let Entry::Occupied(entry) = &mut self {
+            modify(entry.get_mut());
+        }
+
+        self
+    }
+}
+
+impl<'a, VALUE> OccupiedEntry<'a, VALUE> {
+    pub fn get_mut(&mut self) -> &mut VALUE {
+        self.value
+    }
+
+    pub fn into_mut(self) -> &'a mut VALUE {
+        self.value
+    }
+
+    // replaces the value, returning the previous one
+    pub fn insert(&mut self, value: VALUE) -> VALUE {
+        std::mem::replace(self.get_mut(), value)
+    }
+}
+
+impl<'a, KEY, VALUE> VacantEntry<'a, KEY, VALUE> {
+    pub fn into_key(self) -> KEY {
+        self.key
+    }
+
+    pub fn insert(self, value: VALUE) -> &'a mut VALUE {
+        for size in self.sizes {
+            *size += 1;
+        }
+
+        if let Some(inserted) = self.inserted {
+            *inserted = Some(self.position);
+        }
+
+        self.slot.fill(self.key, value)
+    }
+}
@@ -1,7 +1,13 @@
 pub mod bst;
+pub mod entry;
+pub mod node;
 pub mod red_black_bst;
 pub mod test_client;
 
+use crate::error::Result;
+use std::borrow::Cow;
+use std::ops::{Bound, RangeBounds};
+
 pub trait ST<KEY: Ord, VALUE> {
     fn new() -> Self;
     fn put(&mut self, key: KEY, value: VALUE); // should receive ownership of the values to store
@@ -13,7 +19,25 @@ pub trait ST<KEY: Ord, VALUE> {
     fn ceiling(&self, key: &KEY) -> Option<&KEY>;
     fn select(&self, position: usize) -> Option<&KEY>;
     fn rank(&self, key: &KEY) -> Option<usize>;
-    fn keys_in_range(&self, min_key: &KEY, max_key: &KEY) -> Vec<&KEY>;
+    // lazy iteration over entries without materializing keys
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a;
+    fn range<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a;
+    fn rev<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a;
+    fn delete(&mut self, key: &KEY);
+    fn delete_min(&mut self);
+    fn delete_max(&mut self);
 
     fn is_empty(&self) -> bool {
         self.size() == 0
@@ -23,23 +47,92 @@ pub trait ST<KEY: Ord, VALUE> {
         self.get(key).is_some()
     }
 
-    fn delete(&mut self, _key: &KEY) {
-        // missing implementation
+    fn keys<'a>(&'a self) -> Vec<&'a KEY>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        self.iter().map(|(key, _)| key).collect()
+    }
+
+    fn keys_in_range<'a>(&'a self, min_key: &KEY, max_key: &KEY) -> Vec<&'a KEY>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        self.range((Bound::Included(min_key), Bound::Included(max_key)))
+            .map(|(key, _)| key)
+            .collect()
     }
+}
+
+// Read half of ST for a table kept on disk. Min and max come from the key range the table
+// keeps in memory, while the other reads go to the file and may fail, so they hand over
+// owned keys and values.
+pub trait ReadOnlyST<KEY: Ord, VALUE> {
+    fn size(&self) -> usize;
+    fn get(&self, key: &KEY) -> Result<Option<VALUE>>;
+    fn min(&self) -> Option<&KEY>;
+    fn max(&self) -> Option<&KEY>;
+    fn floor(&self, key: &KEY) -> Result<Option<KEY>>;
+    fn ceiling(&self, key: &KEY) -> Result<Option<KEY>>;
+    fn keys_in_range(&self, min_key: &KEY, max_key: &KEY) -> Result<Vec<KEY>>;
+    // lazy iteration over entries in ascending key order
+    fn iter<'a>(&'a self) -> impl Iterator<Item = Result<(KEY, VALUE)>>
+    where
+        KEY: 'a,
+        VALUE: 'a;
 
-    fn keys(&self) -> Vec<&KEY> {
-        let keys = Vec::new();
+    fn is_empty(&self) -> bool {
+        self.size() == 0
+    }
 
-        let min_key = self.min();
-        let max_key = self.max();
+    fn contains(&self, key: &KEY) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+}
 
-        if min_key.is_none() || max_key.is_none() {
-            return keys;
-        }
+// Symbol table whose operations report failures instead of panicking, as those of a
+// store backed by disk may fail. In-memory trees implement it without ever failing.
+//
+// Reads return Cow, so trees can lend what they hold while stores that decode entries
+// from disk hand over owned copies instead of keeping every entry they read alive.
+//
+// Trees answer size, select and rank from their subtree sizes. A store merging several
+// sources, such as LsmDb, may have to walk its entries for them, in O(n) time.
+pub trait KvStore<KEY: Ord + Clone, VALUE: Clone> {
+    fn put(&mut self, key: KEY, value: VALUE) -> Result<()>;
+    fn get(&self, key: &KEY) -> Result<Option<Cow<'_, VALUE>>>;
+    fn delete(&mut self, key: &KEY) -> Result<()>;
+    fn size(&self) -> Result<usize>;
+    fn min(&self) -> Result<Option<Cow<'_, KEY>>>;
+    fn max(&self) -> Result<Option<Cow<'_, KEY>>>;
+    fn floor(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>>;
+    fn ceiling(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>>;
+    fn select(&self, position: usize) -> Result<Option<Cow<'_, KEY>>>;
+    fn rank(&self, key: &KEY) -> Result<Option<usize>>;
+    // entries in range, in ascending key order
+    fn scan<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = Result<(Cow<'a, KEY>, Cow<'a, VALUE>)>>
+    where
+        KEY: 'a,
+        VALUE: 'a;
+}
 
-        let min_key = min_key.unwrap();
-        let max_key = max_key.unwrap();
+pub fn is_after_start<KEY: Ord, R: RangeBounds<KEY>>(range: &R, key: &KEY) -> bool {
+    match range.start_bound() {
+        Bound::Included(start) => key >= start,
+        Bound::Excluded(start) => key > start,
+        Bound::Unbounded => true,
+    }
+}
 
-        return self.keys_in_range(&min_key, &max_key);
+pub fn is_before_end<KEY: Ord, R: RangeBounds<KEY>>(range: &R, key: &KEY) -> bool {
+    match range.end_bound() {
+        Bound::Included(end) => key <= end,
+        Bound::Excluded(end) => key < end,
+        Bound::Unbounded => true,
     }
 }
@@ -0,0 +1,93 @@
+use crate::stack::Stack;
+use crate::symbol_table::{is_after_start, is_before_end};
+use std::ops::RangeBounds;
+
+// Node of a tree sized by subtree. Iterators walk its parts through shared borrows, while
+// an entry borrows them apart while descending.
+pub trait TreeNode: Sized {
+    type Key: Ord;
+    type Value;
+
+    fn leaf(key: Self::Key, value: Self::Value) -> Self;
+    fn key(&self) -> &Self::Key;
+    fn value(&self) -> &Self::Value;
+    fn size(&self) -> usize;
+    fn left(&self) -> &Option<Self>;
+    fn right(&self) -> &Option<Self>;
+    fn value_mut(&mut self) -> &mut Self::Value;
+    // the subtree size along with the left and right children
+    fn size_and_children_mut(&mut self) -> (&mut usize, &mut Option<Self>, &mut Option<Self>);
+}
+
+pub struct Iter<'a, NODE, R> {
+    stack: Stack<&'a NODE>,
+    range: R,
+    reverse: bool,
+}
+
+impl<'a, NODE, R> Iter<'a, NODE, R>
+where
+    NODE: TreeNode,
+    R: RangeBounds<NODE::Key>,
+{
+    pub fn new(root: &'a Option<NODE>, range: R, reverse: bool) -> Iter<'a, NODE, R> {
+        let mut iter = Iter {
+            stack: Stack::<&NODE>::new::<&NODE>(),
+            range,
+            reverse,
+        };
+
+        iter.push_branch(root.as_ref());
+        iter
+    }
+
+    // stacks the path to the next node on iteration order, skipping subtrees out of range
+    fn push_branch(&mut self, mut node: Option<&'a NODE>) {
+        while let Some(n) = node {
+            if self.reverse {
+                if is_before_end(&self.range, n.key()) {
+                    self.stack.push(n);
+                    node = n.right().as_ref();
+                } else {
+                    node = n.left().as_ref();
+                }
+            } else if is_after_start(&self.range, n.key()) {
+                self.stack.push(n);
+                node = n.left().as_ref();
+            } else {
+                node = n.right().as_ref();
+            }
+        }
+    }
+}
+
+impl<'a, NODE, R> Iterator for Iter<'a, NODE, R>
+where
+    NODE: TreeNode,
+    R: RangeBounds<NODE::Key>,
+{
+    type Item = (&'a NODE::Key, &'a NODE::Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+
+        let in_range = if self.reverse {
+            is_after_start(&self.range, node.key())
+        } else {
+            is_before_end(&self.range, node.key())
+        };
+
+        if !in_range {
+            self.stack = Stack::<&NODE>::new::<&NODE>();
+            return None;
+        }
+
+        if self.reverse {
+            self.push_branch(node.left().as_ref());
+        } else {
+            self.push_branch(node.right().as_ref());
+        }
+
+        Some((node.key(), node.value()))
+    }
+}
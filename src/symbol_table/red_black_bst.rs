@@ -1,8 +1,9 @@
+use crate::error::Result;
+use crate::symbol_table::entry::{self, Entry, InPlace};
+use crate::symbol_table::node::{Iter, TreeNode};
 use crate::symbol_table::ST;
-use std::{
-    ops::{Deref, DerefMut},
-    usize,
-};
+use std::borrow::Cow;
+use std::ops::{Deref, DerefMut, RangeBounds};
 
 pub struct Node<KEY, VALUE>
 where
@@ -32,11 +33,54 @@ where
     }
 }
 
+impl<KEY, VALUE> TreeNode for Node<KEY, VALUE>
+where
+    KEY: Ord,
+{
+    type Key = KEY;
+    type Value = VALUE;
+
+    fn leaf(key: KEY, value: VALUE) -> Node<KEY, VALUE> {
+        Node::new(key, value, 1, true)
+    }
+
+    fn key(&self) -> &KEY {
+        &self.key
+    }
+
+    fn value(&self) -> &VALUE {
+        &self.value
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn left(&self) -> &Option<Self> {
+        &self.left
+    }
+
+    fn right(&self) -> &Option<Self> {
+        &self.right
+    }
+
+    fn value_mut(&mut self) -> &mut VALUE {
+        &mut self.value
+    }
+
+    fn size_and_children_mut(&mut self) -> (&mut usize, &mut Option<Self>, &mut Option<Self>) {
+        (&mut self.size, &mut *self.left, &mut *self.right)
+    }
+}
+
 pub struct RedBlackBST<KEY, VALUE>
 where
     KEY: Ord,
 {
     root: Option<Node<KEY, VALUE>>,
+    // position of a key inserted through an entry and not rebalanced yet, as rotating
+    // would move the value the insert returns; the next write rebalances its path
+    unbalanced: Option<usize>,
 }
 
 impl<KEY, VALUE> RedBlackBST<KEY, VALUE>
@@ -51,15 +95,25 @@ where
         }
     }
 
+    fn is_left_red(node: &Option<Node<KEY, VALUE>>) -> bool {
+        if let Some(v) = node {
+            RedBlackBST::is_red(&v.left)
+        } else {
+            false
+        }
+    }
+
+    // flips h and its children colors: splits a temporary 4-node on insertion
+    // and combines siblings into a 4-node on deletion
     fn flip_colors(h: &mut Node<KEY, VALUE>) {
-        h.is_red = true;
+        h.is_red = !h.is_red;
 
         if let Some(h_left) = h.left.deref_mut() {
-            h_left.is_red = false;
+            h_left.is_red = !h_left.is_red;
         }
 
         if let Some(h_right) = h.right.deref_mut() {
-            h_right.is_red = false;
+            h_right.is_red = !h_right.is_red;
         }
     }
 
@@ -79,7 +133,7 @@ where
         h.size = RedBlackBST::get_size(&h.left) + RedBlackBST::get_size(&h.right) + 1;
 
         x.left.replace(h);
-        return x;
+        x
     }
 
     fn rotate_right(mut h: Node<KEY, VALUE>) -> Node<KEY, VALUE> {
@@ -99,9 +153,10 @@ where
         h.size = RedBlackBST::get_size(&h.left) + RedBlackBST::get_size(&h.right) + 1;
 
         x.right.replace(h);
-        return x;
+        x
     }
 
+    // recursion is as deep as the tree, which balancing keeps under 2 lg n levels
     fn put_node(option_node: &mut Option<Node<KEY, VALUE>>, key: KEY, value: VALUE) {
         if option_node.is_none() {
             option_node.replace(Node::new(key, value, 1, true));
@@ -116,168 +171,265 @@ where
             std::cmp::Ordering::Equal => node.value = value,
         }
 
-        if RedBlackBST::is_red(&node.right) && !RedBlackBST::is_red(&node.left) {
-            node = RedBlackBST::rotate_left(node);
-        }
+        option_node.replace(RedBlackBST::balance(node));
+    }
 
-        if RedBlackBST::is_red(&node.left) {
-            if let Some(left_node) = node.left.deref() {
-                if RedBlackBST::is_red(&left_node.left) {
-                    node = RedBlackBST::rotate_right(node);
-                }
-            }
+    // restores left-leaning red-black invariants on the way up
+    fn balance(mut h: Node<KEY, VALUE>) -> Node<KEY, VALUE> {
+        if RedBlackBST::is_red(&h.right) && !RedBlackBST::is_red(&h.left) {
+            h = RedBlackBST::rotate_left(h);
         }
 
-        if RedBlackBST::is_red(&node.right) && RedBlackBST::is_red(&node.left) {
-            RedBlackBST::flip_colors(&mut node);
+        if RedBlackBST::is_red(&h.left) && RedBlackBST::is_left_red(&h.left) {
+            h = RedBlackBST::rotate_right(h);
         }
 
-        node.size = RedBlackBST::get_size(&node.left) + RedBlackBST::get_size(&node.right) + 1;
+        if RedBlackBST::is_red(&h.right) && RedBlackBST::is_red(&h.left) {
+            RedBlackBST::flip_colors(&mut h);
+        }
 
-        option_node.replace(node);
+        h.size = RedBlackBST::get_size(&h.left) + RedBlackBST::get_size(&h.right) + 1;
+        h
     }
 
-    fn get_size(node: &Box<Option<Node<KEY, VALUE>>>) -> usize {
-        match node.deref() {
-            Some(n) => n.size,
-            None => 0,
-        }
-    }
+    // assuming h is red and both h.left and h.left.left are black,
+    // make h.left or one of its children red
+    fn move_red_left(mut h: Node<KEY, VALUE>) -> Node<KEY, VALUE> {
+        RedBlackBST::flip_colors(&mut h);
 
-    fn get_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a VALUE> {
-        if node.is_none() {
-            return None;
+        if RedBlackBST::is_left_red(&h.right) {
+            let right = h.right.take().unwrap();
+            h.right.replace(RedBlackBST::rotate_right(right));
+            h = RedBlackBST::rotate_left(h);
+            RedBlackBST::flip_colors(&mut h);
         }
 
-        let node = node.as_ref().unwrap();
+        h
+    }
 
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => RedBlackBST::get_node(node.left.deref(), key),
-            std::cmp::Ordering::Greater => RedBlackBST::get_node(node.right.deref(), key),
-            std::cmp::Ordering::Equal => Some(&node.value),
+    // assuming h is red and both h.right and h.right.left are black,
+    // make h.right or one of its children red
+    fn move_red_right(mut h: Node<KEY, VALUE>) -> Node<KEY, VALUE> {
+        RedBlackBST::flip_colors(&mut h);
+
+        if RedBlackBST::is_left_red(&h.left) {
+            h = RedBlackBST::rotate_right(h);
+            RedBlackBST::flip_colors(&mut h);
         }
+
+        h
     }
 
-    fn min_node(node: &Option<Node<KEY, VALUE>>) -> Option<&KEY> {
-        if node.is_none() {
-            return None;
+    // returns the subtree without its minimum node and the detached minimum node
+    fn delete_min_node(mut h: Node<KEY, VALUE>) -> (Option<Node<KEY, VALUE>>, Node<KEY, VALUE>) {
+        if h.left.is_none() {
+            return (None, h);
         }
 
-        let node = node.as_ref().unwrap();
-
-        if node.left.is_none() {
-            return Some(&node.key);
+        if !RedBlackBST::is_red(&h.left) && !RedBlackBST::is_left_red(&h.left) {
+            h = RedBlackBST::move_red_left(h);
         }
 
-        RedBlackBST::min_node(&node.left)
+        let (left, min) = RedBlackBST::delete_min_node(h.left.take().unwrap());
+        *h.left = left;
+
+        (Some(RedBlackBST::balance(h)), min)
     }
 
-    fn max_node(node: &Option<Node<KEY, VALUE>>) -> Option<&KEY> {
-        if node.is_none() {
-            return None;
+    fn delete_max_node(mut h: Node<KEY, VALUE>) -> Option<Node<KEY, VALUE>> {
+        if RedBlackBST::is_red(&h.left) {
+            h = RedBlackBST::rotate_right(h);
         }
 
-        let node = node.as_ref().unwrap();
+        if h.right.is_none() {
+            return None;
+        }
 
-        if node.right.is_none() {
-            return Some(&node.key);
+        if !RedBlackBST::is_red(&h.right) && !RedBlackBST::is_left_red(&h.right) {
+            h = RedBlackBST::move_red_right(h);
         }
 
-        RedBlackBST::max_node(&node.right)
+        *h.right = RedBlackBST::delete_max_node(h.right.take().unwrap());
+
+        Some(RedBlackBST::balance(h))
     }
 
-    pub fn floor_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a KEY> {
-        if node.is_none() {
-            return None;
-        }
+    // key must be present on the subtree rooted at h
+    fn delete_node(mut h: Node<KEY, VALUE>, key: &KEY) -> Option<Node<KEY, VALUE>> {
+        if key < &h.key {
+            if !RedBlackBST::is_red(&h.left) && !RedBlackBST::is_left_red(&h.left) {
+                h = RedBlackBST::move_red_left(h);
+            }
 
-        let node = node.as_ref().unwrap();
+            *h.left = RedBlackBST::delete_node(h.left.take().unwrap(), key);
+        } else {
+            if RedBlackBST::is_red(&h.left) {
+                h = RedBlackBST::rotate_right(h);
+            }
 
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => RedBlackBST::floor_node(node.left.deref(), key),
-            std::cmp::Ordering::Greater => match RedBlackBST::floor_node(node.right.deref(), key) {
-                Some(v) => Some(v),
-                None => Some(&node.key),
-            },
-            std::cmp::Ordering::Equal => Some(&node.key),
+            if key == &h.key && h.right.is_none() {
+                return None;
+            }
+
+            if !RedBlackBST::is_red(&h.right) && !RedBlackBST::is_left_red(&h.right) {
+                h = RedBlackBST::move_red_right(h);
+            }
+
+            if key == &h.key {
+                let (right, min) = RedBlackBST::delete_min_node(h.right.take().unwrap());
+                *h.right = right;
+                h.key = min.key;
+                h.value = min.value;
+            } else {
+                *h.right = RedBlackBST::delete_node(h.right.take().unwrap(), key);
+            }
         }
+
+        Some(RedBlackBST::balance(h))
     }
 
-    fn ceiling_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a KEY> {
-        if node.is_none() {
-            return None;
+    // if both children of root are black, set root to red so deletion can borrow from it
+    fn prepare_root_for_delete(&mut self) -> Option<Node<KEY, VALUE>> {
+        let mut root = self.root.take()?;
+
+        if !RedBlackBST::is_red(&root.left) && !RedBlackBST::is_red(&root.right) {
+            root.is_red = true;
         }
 
-        let node = node.as_ref().unwrap();
+        Some(root)
+    }
 
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => match RedBlackBST::ceiling_node(node.left.deref(), key) {
-                Some(v) => Some(v),
-                None => Some(&node.key),
-            },
-            std::cmp::Ordering::Greater => RedBlackBST::ceiling_node(node.right.deref(), key),
-            std::cmp::Ordering::Equal => Some(&node.key),
+    fn paint_root_black(&mut self) {
+        if let Some(root) = self.root.as_mut() {
+            root.is_red = false;
         }
     }
 
-    fn select_node(node: &Option<Node<KEY, VALUE>>, position: usize) -> Option<&KEY> {
-        let node = node.as_ref().unwrap();
+    fn rebalance(&mut self) {
+        if let Some(position) = self.unbalanced.take() {
+            RedBlackBST::rebalance_node(&mut self.root, position);
+            self.paint_root_black();
+        }
+    }
+
+    // balances the nodes on the path to position on the way back up, as put_node does
+    fn rebalance_node(option_node: &mut Option<Node<KEY, VALUE>>, position: usize) {
+        let mut node = option_node.take().unwrap();
         let left_count = RedBlackBST::get_size(&node.left);
 
         match position.cmp(&left_count) {
-            std::cmp::Ordering::Less => RedBlackBST::select_node(node.left.deref(), position),
+            std::cmp::Ordering::Less => RedBlackBST::rebalance_node(&mut node.left, position),
             std::cmp::Ordering::Greater => {
-                RedBlackBST::select_node(node.right.deref(), position - left_count - 1)
+                RedBlackBST::rebalance_node(&mut node.right, position - left_count - 1)
             }
-            std::cmp::Ordering::Equal => Some(&node.key),
+            std::cmp::Ordering::Equal => {}
         }
+
+        option_node.replace(RedBlackBST::balance(node));
     }
 
-    fn rank_node(node: &Option<Node<KEY, VALUE>>, key: &KEY, position: usize) -> Option<usize> {
-        if node.is_none() {
-            return None;
+    fn get_size(node: &Option<Node<KEY, VALUE>>) -> usize {
+        match node {
+            Some(n) => n.size,
+            None => 0,
         }
+    }
 
-        let node = node.as_ref().unwrap();
-
-        let left_count = RedBlackBST::get_size(&node.left);
+    fn get_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a VALUE> {
+        let mut node = node;
 
-        match key.cmp(&node.key) {
-            std::cmp::Ordering::Less => RedBlackBST::rank_node(node.left.deref(), key, position),
-            std::cmp::Ordering::Greater => {
-                RedBlackBST::rank_node(node.right.deref(), key, position + left_count + 1)
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => node = n.left.deref(),
+                std::cmp::Ordering::Greater => node = n.right.deref(),
+                std::cmp::Ordering::Equal => return Some(&n.value),
             }
-            std::cmp::Ordering::Equal => Some(position + left_count),
         }
+
+        None
     }
 
-    fn keys_node<'a>(
-        result: &mut Vec<&'a KEY>,
-        node: &'a Option<Node<KEY, VALUE>>,
-        min_key: &KEY,
-        max_key: &KEY,
-    ) {
-        if node.is_none() {
-            return;
+    fn min_node(node: &Option<Node<KEY, VALUE>>) -> Option<&KEY> {
+        let mut node = node.as_ref()?;
+
+        while let Some(left) = node.left.deref() {
+            node = left;
         }
 
-        let node = node.as_ref().unwrap();
+        Some(&node.key)
+    }
+
+    fn max_node(node: &Option<Node<KEY, VALUE>>) -> Option<&KEY> {
+        let mut node = node.as_ref()?;
 
-        if &node.key > min_key {
-            RedBlackBST::keys_node(result, &node.left.as_ref(), min_key, max_key);
+        while let Some(right) = node.right.deref() {
+            node = right;
         }
 
-        if &node.key >= min_key && &node.key <= max_key {
-            result.push(&node.key);
+        Some(&node.key)
+    }
+
+    pub fn floor_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a KEY> {
+        let mut node = node;
+        let mut floor = None; // greatest key smaller than key seen on the way down
+
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => node = n.left.deref(),
+                std::cmp::Ordering::Greater => {
+                    floor = Some(&n.key);
+                    node = n.right.deref();
+                }
+                std::cmp::Ordering::Equal => return Some(&n.key),
+            }
+        }
+
+        floor
+    }
+
+    fn ceiling_node<'a>(node: &'a Option<Node<KEY, VALUE>>, key: &KEY) -> Option<&'a KEY> {
+        let mut node = node;
+        let mut ceiling = None; // smallest key greater than key seen on the way down
+
+        while let Some(n) = node {
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => {
+                    ceiling = Some(&n.key);
+                    node = n.left.deref();
+                }
+                std::cmp::Ordering::Greater => node = n.right.deref(),
+                std::cmp::Ordering::Equal => return Some(&n.key),
+            }
         }
 
-        if &node.key < max_key {
-            RedBlackBST::keys_node(result, &node.right.as_ref(), min_key, max_key);
+        ceiling
+    }
+
+    // position must be less than the size of the subtree
+    fn select_node(node: &Option<Node<KEY, VALUE>>, mut position: usize) -> Option<&KEY> {
+        let mut node = node.as_ref().unwrap();
+
+        loop {
+            let left_count = RedBlackBST::get_size(&node.left);
+
+            match position.cmp(&left_count) {
+                std::cmp::Ordering::Less => node = node.left.deref().as_ref().unwrap(),
+                std::cmp::Ordering::Greater => {
+                    position -= left_count + 1;
+                    node = node.right.deref().as_ref().unwrap();
+                }
+                std::cmp::Ordering::Equal => return Some(&node.key),
+            }
         }
     }
 
+    // lazy iteration over the range in descending key order
+    pub fn range_rev<R: RangeBounds<KEY>>(&self, range: R) -> Iter<'_, Node<KEY, VALUE>, R> {
+        Iter::new(&self.root, range, true)
+    }
+
     // don't take this too seriously
-    #[allow(dead_code)]
+    #[cfg(test)]
     pub fn draw_node(
         node: &Option<Node<String, VALUE>>,
         position: usize,
@@ -289,7 +441,7 @@ where
         }
 
         let node = node.as_ref().unwrap();
-        let width = 200 / (2 as usize).pow(level as u32);
+        let width = 200 / 2_usize.pow(level as u32);
         let padding = width * position + width / 2;
 
         if result.len() <= level {
@@ -341,11 +493,16 @@ where
     VALUE: Clone,
 {
     fn new() -> RedBlackBST<KEY, VALUE> {
-        RedBlackBST { root: None }
+        RedBlackBST {
+            root: None,
+            unbalanced: None,
+        }
     }
 
     fn put(&mut self, key: KEY, value: VALUE) {
+        self.rebalance();
         RedBlackBST::put_node(&mut self.root, key, value);
+        self.paint_root_black();
     }
 
     fn size(&self) -> usize {
@@ -384,24 +541,349 @@ where
     }
 
     fn rank(&self, key: &KEY) -> Option<usize> {
-        RedBlackBST::rank_node(&self.root, key, 0)
+        let mut node = &self.root;
+        let mut position = 0;
+
+        while let Some(n) = node {
+            let left_count = RedBlackBST::get_size(&n.left);
+
+            match key.cmp(&n.key) {
+                std::cmp::Ordering::Less => node = n.left.deref(),
+                std::cmp::Ordering::Greater => {
+                    position += left_count + 1;
+                    node = n.right.deref();
+                }
+                std::cmp::Ordering::Equal => return Some(position + left_count),
+            }
+        }
+
+        None
+    }
+
+    fn iter<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        Iter::new(&self.root, .., false)
     }
 
-    fn keys_in_range(&self, min_key: &KEY, max_key: &KEY) -> Vec<&KEY> {
-        let mut keys = Vec::new();
-        RedBlackBST::keys_node(&mut keys, &self.root, &min_key, &max_key);
-        return keys;
+    fn range<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        Iter::new(&self.root, range, false)
+    }
+
+    fn rev<'a>(&'a self) -> impl Iterator<Item = (&'a KEY, &'a VALUE)>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        Iter::new(&self.root, .., true)
+    }
+
+    fn delete(&mut self, key: &KEY) {
+        self.rebalance();
+        if RedBlackBST::get_node(&self.root, key).is_none() {
+            return;
+        }
+
+        if let Some(root) = self.prepare_root_for_delete() {
+            self.root = RedBlackBST::delete_node(root, key);
+            self.paint_root_black();
+        }
+    }
+
+    fn delete_min(&mut self) {
+        self.rebalance();
+        if let Some(root) = self.prepare_root_for_delete() {
+            self.root = RedBlackBST::delete_min_node(root).0;
+            self.paint_root_black();
+        }
+    }
+
+    fn delete_max(&mut self) {
+        self.rebalance();
+        if let Some(root) = self.prepare_root_for_delete() {
+            self.root = RedBlackBST::delete_max_node(root);
+            self.paint_root_black();
+        }
+    }
+}
+
+impl<KEY, VALUE> super::KvStore<KEY, VALUE> for RedBlackBST<KEY, VALUE>
+where
+    KEY: Ord + Clone,
+    VALUE: Clone,
+{
+    fn put(&mut self, key: KEY, value: VALUE) -> Result<()> {
+        ST::put(self, key, value);
+        Ok(())
+    }
+
+    fn get(&self, key: &KEY) -> Result<Option<Cow<'_, VALUE>>> {
+        Ok(ST::get(self, key).map(Cow::Borrowed))
+    }
+
+    fn delete(&mut self, key: &KEY) -> Result<()> {
+        ST::delete(self, key);
+        Ok(())
+    }
+
+    fn size(&self) -> Result<usize> {
+        Ok(ST::size(self))
+    }
+
+    fn min(&self) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::min(self).map(Cow::Borrowed))
+    }
+
+    fn max(&self) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::max(self).map(Cow::Borrowed))
+    }
+
+    fn floor(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::floor(self, key).map(Cow::Borrowed))
+    }
+
+    fn ceiling(&self, key: &KEY) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::ceiling(self, key).map(Cow::Borrowed))
+    }
+
+    fn select(&self, position: usize) -> Result<Option<Cow<'_, KEY>>> {
+        Ok(ST::select(self, position).map(Cow::Borrowed))
+    }
+
+    fn rank(&self, key: &KEY) -> Result<Option<usize>> {
+        Ok(ST::rank(self, key))
+    }
+
+    fn scan<'a, R: RangeBounds<KEY>>(
+        &'a self,
+        range: R,
+    ) -> impl Iterator<Item = Result<(Cow<'a, KEY>, Cow<'a, VALUE>)>>
+    where
+        KEY: 'a,
+        VALUE: 'a,
+    {
+        ST::range(self, range).map(|(key, value)| Ok((Cow::Borrowed(key), Cow::Borrowed(value))))
+    }
+}
+
+impl<KEY, VALUE> InPlace<KEY, VALUE> for RedBlackBST<KEY, VALUE>
+where
+    KEY: Ord,
+{
+    fn entry(&mut self, key: KEY) -> Entry<'_, KEY, VALUE> {
+        self.rebalance();
+        entry::descend(&mut self.root, key, Some(&mut self.unbalanced))
+    }
+
+    fn get_mut(&mut self, key: &KEY) -> Option<&mut VALUE> {
+        entry::find_mut(&mut self.root, key)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::symbol_table::test_client::symbol_table_integration::run_tests;
+    use crate::symbol_table::test_client::symbol_table_integration::{run_entry_tests, run_tests};
 
     #[test]
     fn run_integration_tests() {
         run_tests::<RedBlackBST<String, String>>();
+        run_entry_tests::<RedBlackBST<String, String>>();
+    }
+
+    // every path from root to a leaf has the same number of black links
+    fn black_height(node: &Option<Node<String, String>>) -> Option<usize> {
+        match node {
+            None => Some(0),
+            Some(n) => {
+                let left = black_height(&n.left)?;
+                let right = black_height(&n.right)?;
+
+                if left != right {
+                    return None;
+                }
+
+                Some(left + if n.is_red { 0 } else { 1 })
+            }
+        }
+    }
+
+    // no right-leaning red links and no two red links in a row
+    fn is_23(node: &Option<Node<String, String>>, is_root: bool) -> bool {
+        match node {
+            None => true,
+            Some(n) => {
+                if RedBlackBST::is_red(&n.right) {
+                    return false;
+                }
+
+                if !is_root && n.is_red && RedBlackBST::is_red(&n.left) {
+                    return false;
+                }
+
+                is_23(&n.left, false) && is_23(&n.right, false)
+            }
+        }
+    }
+
+    fn is_size_consistent(node: &Option<Node<String, String>>) -> bool {
+        match node {
+            None => true,
+            Some(n) => {
+                n.size == RedBlackBST::get_size(&n.left) + RedBlackBST::get_size(&n.right) + 1
+                    && is_size_consistent(&n.left)
+                    && is_size_consistent(&n.right)
+            }
+        }
+    }
+
+    fn assert_invariants(st: &RedBlackBST<String, String>) {
+        assert!(!RedBlackBST::is_red(&st.root));
+        assert!(black_height(&st.root).is_some());
+        assert!(is_23(&st.root, true));
+        assert!(is_size_consistent(&st.root));
+    }
+
+    #[test]
+    fn delete_keeps_order_rank_and_select() {
+        // arrange
+        let st = &mut RedBlackBST::<String, String>::new();
+        let keys = "S E A R C H E X A M P L E".split(" ");
+
+        for (position, key) in keys.enumerate() {
+            st.put(String::from(key), format!("{}", position));
+        }
+
+        // act
+        st.delete(&String::from("E"));
+        st.delete(&String::from("R"));
+        st.delete(&String::from("Z"));
+
+        // assert
+        assert_invariants(st);
+        assert_eq!(st.size(), 8);
+        assert!(!st.contains(&String::from("E")));
+        assert!(!st.contains(&String::from("R")));
+        assert_eq!(st.get(&String::from("S")), Some(&String::from("0")));
+
+        let keys: Vec<&String> = st.keys();
+        assert_eq!(keys, vec!["A", "C", "H", "L", "M", "P", "S", "X"]);
+        assert_eq!(st.rank(&String::from("H")), Some(2));
+        assert_eq!(st.rank(&String::from("S")), Some(6));
+        assert_eq!(st.select(2), Some(&String::from("H")));
+        assert_eq!(st.select(7), Some(&String::from("X")));
+        assert_eq!(st.select(8), None);
+    }
+
+    #[test]
+    fn delete_min_and_max_remove_extremes() {
+        // arrange
+        let st = &mut RedBlackBST::<String, String>::new();
+        let keys = "S E A R C H E X A M P L E".split(" ");
+
+        for (position, key) in keys.enumerate() {
+            st.put(String::from(key), format!("{}", position));
+        }
+
+        // act
+        st.delete_min();
+        st.delete_max();
+
+        // assert
+        assert_invariants(st);
+        assert_eq!(st.size(), 8);
+        assert_eq!(st.min(), Some(&String::from("C")));
+        assert_eq!(st.max(), Some(&String::from("S")));
+    }
+
+    #[test]
+    fn delete_every_key_in_any_order_keeps_tree_balanced() {
+        // arrange
+        let st = &mut RedBlackBST::<String, String>::new();
+        let total = 200;
+
+        for i in 0..total {
+            st.put(format!("{:03}", (i * 37) % total), format!("{}", i));
+        }
+
+        // act & assert
+        for i in 0..total {
+            let key = format!("{:03}", (i * 91) % total);
+            st.delete(&key);
+
+            assert_invariants(st);
+            assert_eq!(st.size(), total - i - 1);
+            assert!(!st.contains(&key));
+        }
+
+        assert!(st.is_empty());
+
+        st.delete_min();
+        st.delete_max();
+        assert!(st.is_empty());
+    }
+
+    #[test]
+    fn entry_inserts_are_rebalanced_by_the_next_write() {
+        // arrange
+        let st = &mut RedBlackBST::<String, String>::new();
+        let total = 200;
+
+        // act & assert
+        for i in 0..total {
+            let key = format!("{:03}", (i * 37) % total);
+            let value = st.entry(key.clone()).or_insert(String::new());
+            value.push_str(&key);
+
+            assert!(is_size_consistent(&st.root));
+            assert_eq!(st.size(), i + 1);
+            assert_eq!(st.get(&key), Some(&key));
+        }
+
+        for i in 0..total {
+            st.entry(format!("{:03}", i))
+                .and_modify(|value| value.push('!'))
+                .or_insert(String::new());
+        }
+
+        assert_invariants(st);
+        assert_eq!(st.size(), total);
+        assert!(st.iter().all(|(key, value)| *value == format!("{}!", key)));
+
+        st.entry(format!("{:03}", total)).or_insert(String::new());
+        st.delete_min();
+
+        assert_invariants(st);
+        assert_eq!(st.size(), total);
+    }
+
+    #[test]
+    fn range_rev_iterates_range_in_descending_order() {
+        // arrange
+        let st = &mut RedBlackBST::<String, String>::new();
+        let keys = "S E A R C H E X A M P L E".split(" ");
+
+        for (position, key) in keys.enumerate() {
+            st.put(String::from(key), format!("{}", position));
+        }
+
+        // act
+        let keys: Vec<&String> = st
+            .range_rev(String::from("D")..String::from("R"))
+            .map(|(key, _)| key)
+            .collect();
+
+        // assert
+        assert_eq!(keys, vec!["P", "M", "L", "H", "E"]);
     }
 
     #[test]
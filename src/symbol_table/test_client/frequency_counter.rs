@@ -1,47 +1,115 @@
-use crate::symbol_table::ST;
+use crate::error::{Error, Result};
+use crate::lsm::db::LsmDb;
+use crate::lsm::snapshot::Snapshot;
+use crate::symbol_table::entry::InPlace;
+use crate::symbol_table::KvStore;
 use std::fs::File;
-use std::io::{prelude::*, BufReader};
+use std::io::{self, prelude::*, BufReader};
+use std::path::PathBuf;
 
 pub struct FrequencyCounter {}
 
 impl FrequencyCounter {
-    pub fn build(st: &mut impl ST<String, u64>, file_path: &str, min_length: usize) -> u64 {
-        let file = File::open(file_path).expect("file not found");
+    pub fn build(
+        st: &mut impl KvStore<String, u64>,
+        file_path: &str,
+        min_length: usize,
+    ) -> Result<u64> {
+        FrequencyCounter::for_each_word(file_path, min_length, |word| {
+            let frequency = st.get(&word)?.map_or(0, |frequency| *frequency);
+            st.put(word, frequency + 1)
+        })
+    }
+
+    // counts with a merge of 1 per word instead of reading the count to write it back,
+    // the database needs a merge operator adding u64 operands
+    pub fn build_merged(
+        db: &mut LsmDb<String, u64>,
+        file_path: &str,
+        min_length: usize,
+    ) -> Result<u64> {
+        FrequencyCounter::for_each_word(file_path, min_length, |word| db.merge(word, 1))
+    }
+
+    // adds the count of every word the snapshot sees starting with an uppercase ascii
+    // letter to the count of its lowercase form, deleting it, all in one transaction.
+    // Returns how many words were folded.
+    pub fn fold_case(db: &mut LsmDb<String, u64>, snapshot: &Snapshot) -> Result<usize> {
+        let capitalized = db
+            .range_at(String::from("A")..String::from("["), snapshot)
+            .map(|entry| entry.map(|(word, _)| word))
+            .collect::<Result<Vec<String>>>()?;
+
+        let mut transaction = db.begin();
+
+        for word in &capitalized {
+            let lowercase = word.to_lowercase();
+            let count = transaction.get(db, word)?.unwrap_or(0);
+            let folded = transaction.get(db, &lowercase)?.unwrap_or(0);
+
+            transaction.put(lowercase, folded + count);
+            transaction.delete(word.clone());
+        }
+
+        db.commit(transaction)?;
+        Ok(capitalized.len())
+    }
+
+    // counts through the entry of every word, updating the count in place instead of
+    // reading it and putting back a new one
+    pub fn build_in_place(
+        st: &mut impl InPlace<String, u64>,
+        file_path: &str,
+        min_length: usize,
+    ) -> Result<u64> {
+        FrequencyCounter::for_each_word(file_path, min_length, |word| {
+            *st.entry(word).or_insert(0) += 1;
+            Ok(())
+        })
+    }
+
+    // feeds the words of the file at least min_length long to apply, returning how many
+    fn for_each_word(
+        file_path: &str,
+        min_length: usize,
+        mut apply: impl FnMut(String) -> Result<()>,
+    ) -> Result<u64> {
+        let file = File::open(file_path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => Error::NotFound(PathBuf::from(file_path)),
+            _ => Error::Io(e),
+        })?;
         let reader = BufReader::new(file);
         let mut count: u64 = 0;
 
         for line in reader.lines() {
-            for word in line.unwrap().split(" ") {
+            for word in line?.split(" ") {
                 let word = String::from(word);
 
-                if &word.len() < &min_length {
+                if word.len() < min_length {
                     continue;
                 }
-                if !st.contains(&word) {
-                    st.put(word.clone(), 1);
-                } else {
-                    st.put(word.clone(), st.get(&word).unwrap() + 1);
-                }
+                apply(word)?;
 
                 count += 1;
             }
         }
 
-        count
+        Ok(count)
     }
 
-    pub fn find_max(st: &mut impl ST<String, u64>) -> String {
+    pub fn find_max(st: &impl KvStore<String, u64>) -> Result<String> {
         let mut max = String::new();
+        let mut max_count = 0;
 
-        st.put(max.clone(), 0);
+        for entry in st.scan(..) {
+            let (word, count) = entry?;
 
-        let keys = st.keys();
-        for word in keys {
-            if st.get(&word).unwrap() > st.get(&max).unwrap() {
-                max = word.clone();
+            if *count > max_count {
+                max = word.into_owned();
+                max_count = *count;
             }
         }
 
-        max
+        Ok(max)
     }
 }
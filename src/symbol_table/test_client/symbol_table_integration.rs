@@ -1,9 +1,12 @@
-use crate::symbol_table::ST;
+use crate::error::Result;
+use crate::symbol_table::entry::{Entry, InPlace};
+use crate::symbol_table::{KvStore, ST};
+use std::borrow::Cow;
 
 #[allow(dead_code)]
 pub fn run_tests<T>()
 where
-    T: ST<String, String>,
+    T: ST<String, String> + KvStore<String, String>,
 {
     symbol_table_iterate_keys_ordered::<T>();
     symbol_table_iterate_keys_ordered_between_range::<T>();
@@ -14,6 +17,220 @@ where
     floor_can_find_lower_or_equal_key::<T>();
     ceiling_can_find_greater_or_equal_key::<T>();
     delete_values_restore_symbol_table_to_empty::<T>();
+    delete_keeps_keys_ordered_and_ranked::<T>();
+    delete_min_and_max_remove_extremes::<T>();
+    iterate_entries_in_order::<T>();
+    iterate_entries_in_range::<T>();
+    iterate_entries_in_reverse_order::<T>();
+    run_store_tests(T::new);
+}
+
+// KvStore reads, for stores created by new, which may keep their entries on disk
+#[allow(dead_code)]
+pub fn run_store_tests<T>(new: impl Fn() -> T)
+where
+    T: KvStore<String, String>,
+{
+    store_reads_values_by_key(&new);
+    store_finds_keys_by_order(&new);
+    store_scans_entries_in_range(&new);
+    store_deletes_hide_keys_from_reads(&new);
+}
+
+// entries of trees whose values can be updated in place
+#[allow(dead_code)]
+pub fn run_entry_tests<T>()
+where
+    T: ST<String, String> + InPlace<String, String>,
+{
+    entries_insert_missing_keys_in_order::<T>();
+    entries_modify_values_in_place::<T>();
+}
+
+fn entries_insert_missing_keys_in_order<T>()
+where
+    T: ST<String, String> + InPlace<String, String>,
+{
+    // arrange
+    let st = &mut T::new();
+    let keys = "S E A R C H E X A M P L E".split(" ");
+
+    // act
+    for (position, key) in keys.enumerate() {
+        st.entry(String::from(key))
+            .or_insert_with(|| format!("{}", position));
+    }
+
+    // assert
+    assert_eq!(st.size(), 10);
+    assert_eq!(st.get(&String::from("E")), Some(&String::from("1")));
+    assert_eq!(st.get(&String::from("A")), Some(&String::from("2")));
+    assert_eq!(
+        st.keys(),
+        vec!["A", "C", "E", "H", "L", "M", "P", "R", "S", "X"]
+    );
+    assert_eq!(st.rank(&String::from("S")), Some(8));
+}
+
+fn entries_modify_values_in_place<T>()
+where
+    T: ST<String, String> + InPlace<String, String>,
+{
+    // arrange
+    let st = &mut T::new();
+
+    for key in "S E A R C H".split(" ") {
+        st.put(String::from(key), String::from(key));
+    }
+
+    // act
+    st.entry(String::from("E"))
+        .and_modify(|value| value.push('!'))
+        .or_insert(String::from("missing"));
+    st.entry(String::from("X"))
+        .and_modify(|value| value.push('!'))
+        .or_insert(String::from("missing"));
+    st.get_mut(&String::from("A")).unwrap().push('?');
+    st.entry(String::from("D"))
+        .or_insert(String::from("D"))
+        .push('+');
+
+    let replaced = match st.entry(String::from("C")) {
+        Entry::Occupied(mut entry) => entry.insert(String::from("c")),
+        Entry::Vacant(_) => panic!("C is in the tree"),
+    };
+    let vacant = match st.entry(String::from("B")) {
+        Entry::Occupied(_) => panic!("B is not in the tree"),
+        Entry::Vacant(entry) => entry.into_key(),
+    };
+
+    // assert
+    assert_eq!(st.get(&String::from("E")), Some(&String::from("E!")));
+    assert_eq!(st.get(&String::from("X")), Some(&String::from("missing")));
+    assert_eq!(st.get(&String::from("A")), Some(&String::from("A?")));
+    assert_eq!(st.get(&String::from("C")), Some(&String::from("c")));
+    assert_eq!(st.get(&String::from("D")), Some(&String::from("D+")));
+    assert_eq!(st.rank(&String::from("E")), Some(3));
+    assert_eq!(replaced, "C");
+    assert_eq!(vacant, "B");
+    assert_eq!(st.get_mut(&String::from("B")), None);
+    assert_eq!(st.size(), 8);
+}
+
+fn store_with_example_keys<T>(new: &impl Fn() -> T) -> T
+where
+    T: KvStore<String, String>,
+{
+    let mut store = new();
+    let keys = "S E A R C H E X A M P L E".split(" ");
+
+    for (position, key) in keys.enumerate() {
+        store
+            .put(String::from(key), format!("{}", position))
+            .unwrap();
+    }
+
+    store
+}
+
+fn owned<KEY: Clone>(key: Result<Option<Cow<KEY>>>) -> Option<KEY> {
+    key.unwrap().map(Cow::into_owned)
+}
+
+fn store_reads_values_by_key<T>(new: &impl Fn() -> T)
+where
+    T: KvStore<String, String>,
+{
+    // arrange
+    let store = store_with_example_keys(new);
+
+    // act
+    let found = store.get(&String::from("E")).unwrap();
+    let missing = store.get(&String::from("T")).unwrap();
+
+    // assert
+    assert_eq!(found.as_deref(), Some(&String::from("12")));
+    assert_eq!(missing, None);
+    assert_eq!(store.size().unwrap(), 10);
+}
+
+fn store_finds_keys_by_order<T>(new: &impl Fn() -> T)
+where
+    T: KvStore<String, String>,
+{
+    // arrange
+    let store = store_with_example_keys(new);
+
+    // assert
+    assert_eq!(owned(store.min()), Some(String::from("A")));
+    assert_eq!(owned(store.max()), Some(String::from("X")));
+    assert_eq!(
+        owned(store.floor(&String::from("J"))),
+        Some(String::from("H"))
+    );
+    assert_eq!(owned(store.floor(&String::from("0"))), None);
+    assert_eq!(
+        owned(store.ceiling(&String::from("D"))),
+        Some(String::from("E"))
+    );
+    assert_eq!(owned(store.ceiling(&String::from("Z"))), None);
+    assert_eq!(owned(store.select(3)), Some(String::from("H")));
+    assert_eq!(owned(store.select(10)), None);
+    assert_eq!(store.rank(&String::from("S")).unwrap(), Some(8));
+    assert_eq!(store.rank(&String::from("G")).unwrap(), None);
+}
+
+fn store_scans_entries_in_range<T>(new: &impl Fn() -> T)
+where
+    T: KvStore<String, String>,
+{
+    // arrange
+    let store = store_with_example_keys(new);
+
+    // act
+    let entries: Vec<(String, String)> = store
+        .scan(String::from("D")..=String::from("M"))
+        .map(|entry| {
+            let (key, value) = entry.unwrap();
+            (key.into_owned(), value.into_owned())
+        })
+        .collect();
+    let all = store.scan(..).count();
+
+    // assert
+    assert_eq!(
+        entries,
+        vec![
+            (String::from("E"), String::from("12")),
+            (String::from("H"), String::from("5")),
+            (String::from("L"), String::from("11")),
+            (String::from("M"), String::from("9"))
+        ]
+    );
+    assert_eq!(all, 10);
+}
+
+fn store_deletes_hide_keys_from_reads<T>(new: &impl Fn() -> T)
+where
+    T: KvStore<String, String>,
+{
+    // arrange
+    let mut store = store_with_example_keys(new);
+
+    // act
+    store.delete(&String::from("A")).unwrap();
+    store.delete(&String::from("R")).unwrap();
+    store.delete(&String::from("T")).unwrap();
+
+    // assert
+    assert_eq!(store.size().unwrap(), 8);
+    assert_eq!(store.get(&String::from("A")).unwrap(), None);
+    assert_eq!(owned(store.min()), Some(String::from("C")));
+    assert_eq!(
+        owned(store.floor(&String::from("R"))),
+        Some(String::from("P"))
+    );
+    assert_eq!(store.rank(&String::from("S")).unwrap(), Some(6));
 }
 
 fn symbol_table_iterate_keys_ordered<T>()
@@ -213,6 +430,162 @@ where
     st.delete(&String::from("test"));
 
     // assert
-    // assert!(st.is_empty());
-    // assert!(!st.contains(String::from("test")));
+    assert!(st.is_empty());
+    assert!(!st.contains(&String::from("test")));
+}
+
+fn delete_keeps_keys_ordered_and_ranked<T>()
+where
+    T: ST<String, String>,
+{
+    // arrange
+    let st = &mut T::new();
+    let keys = "S E A R C H E X A M P L E".split(" ");
+
+    for (position, key) in keys.enumerate() {
+        st.put(String::from(key), format!("{}", position));
+    }
+
+    // act
+    st.delete(&String::from("E"));
+    st.delete(&String::from("S"));
+    st.delete(&String::from("T"));
+
+    // assert
+    assert_eq!(st.size(), 8);
+    assert!(!st.contains(&String::from("E")));
+    assert!(!st.contains(&String::from("S")));
+    assert_eq!(st.get(&String::from("R")), Some(&String::from("3")));
+
+    let keys = st.keys();
+    let mut iter = keys.iter();
+    assert_eq!(iter.next(), Some(&&String::from("A")));
+    assert_eq!(iter.next(), Some(&&String::from("C")));
+    assert_eq!(iter.next(), Some(&&String::from("H")));
+    assert_eq!(iter.next(), Some(&&String::from("L")));
+    assert_eq!(iter.next(), Some(&&String::from("M")));
+    assert_eq!(iter.next(), Some(&&String::from("P")));
+    assert_eq!(iter.next(), Some(&&String::from("R")));
+    assert_eq!(iter.next(), Some(&&String::from("X")));
+    assert_eq!(iter.next(), None);
+
+    assert_eq!(st.rank(&String::from("H")), Some(2));
+    assert_eq!(st.rank(&String::from("X")), Some(7));
+    assert_eq!(st.select(6), Some(&String::from("R")));
+    assert_eq!(st.select(8), None);
+}
+
+fn delete_min_and_max_remove_extremes<T>()
+where
+    T: ST<String, String>,
+{
+    // arrange
+    let st = &mut T::new();
+    let keys = "S E A R C H E X A M P L E".split(" ");
+
+    for (position, key) in keys.enumerate() {
+        st.put(String::from(key), format!("{}", position));
+    }
+
+    // act
+    st.delete_min();
+    st.delete_max();
+
+    // assert
+    assert_eq!(st.size(), 8);
+    assert_eq!(st.min(), Some(&String::from("C")));
+    assert_eq!(st.max(), Some(&String::from("S")));
+    assert_eq!(st.rank(&String::from("C")), Some(0));
+
+    // act
+    for _ in 0..8 {
+        st.delete_min();
+    }
+    st.delete_min();
+    st.delete_max();
+
+    // assert
+    assert!(st.is_empty());
+    assert_eq!(st.min(), None);
+    assert_eq!(st.max(), None);
+}
+
+fn iterate_entries_in_order<T>()
+where
+    T: ST<String, String>,
+{
+    // arrange
+    let st = &mut T::new();
+    let keys = "S E A R C H E X A M P L E".split(" ");
+
+    for (position, key) in keys.enumerate() {
+        st.put(String::from(key), format!("{}", position));
+    }
+
+    // act
+    let mut iter = st.iter();
+
+    // assert
+    assert_eq!(iter.next(), Some((&String::from("A"), &String::from("8"))));
+    assert_eq!(iter.next(), Some((&String::from("C"), &String::from("4"))));
+    assert_eq!(iter.next(), Some((&String::from("E"), &String::from("12"))));
+    assert_eq!(iter.count(), 7);
+    assert_eq!(T::new().iter().next(), None);
+}
+
+fn iterate_entries_in_range<T>()
+where
+    T: ST<String, String>,
+{
+    // arrange
+    let st = &mut T::new();
+    let keys = "S E A R C H E X A M P L E".split(" ");
+
+    for (position, key) in keys.enumerate() {
+        st.put(String::from(key), format!("{}", position));
+    }
+
+    // act
+    let inclusive: Vec<&String> = st
+        .range(String::from("D")..=String::from("M"))
+        .map(|(key, _)| key)
+        .collect();
+    let exclusive: Vec<&String> = st
+        .range(String::from("E")..String::from("M"))
+        .map(|(key, _)| key)
+        .collect();
+    let from: Vec<&String> = st.range(String::from("R")..).map(|(key, _)| key).collect();
+    let empty: Vec<&String> = st
+        .range(String::from("Y")..=String::from("Z"))
+        .map(|(key, _)| key)
+        .collect();
+
+    // assert
+    assert_eq!(inclusive, vec!["E", "H", "L", "M"]);
+    assert_eq!(exclusive, vec!["E", "H", "L"]);
+    assert_eq!(from, vec!["R", "S", "X"]);
+    assert!(empty.is_empty());
+}
+
+fn iterate_entries_in_reverse_order<T>()
+where
+    T: ST<String, String>,
+{
+    // arrange
+    let st = &mut T::new();
+    let keys = "S E A R C H E X A M P L E".split(" ");
+
+    for (position, key) in keys.enumerate() {
+        st.put(String::from(key), format!("{}", position));
+    }
+
+    // act
+    let keys: Vec<&String> = st.rev().map(|(key, _)| key).collect();
+
+    // assert
+    assert_eq!(keys, vec!["X", "S", "R", "P", "M", "L", "H", "E", "C", "A"]);
+    assert_eq!(
+        st.rev().next(),
+        Some((&String::from("X"), &String::from("7")))
+    );
 }